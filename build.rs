@@ -1,3 +1,5 @@
+use std::{env, fs, path::Path};
+
 fn main() {
     #[cfg(windows)]
     {
@@ -10,4 +12,50 @@ fn main() {
         println!("cargo:rust-cfg=host_unix");
         println!("cargo:rust-cfg=host_family=unix");
     }
+
+    generate_emoji_table();
+}
+
+/// Writes a table of every bundled emoji image, sorted by name so it can be binary searched.
+fn generate_emoji_table() {
+    let manifest_dir = env::var("CARGO_MANIFEST_DIR").expect("cargo should set CARGO_MANIFEST_DIR");
+    let emoji_dir = Path::new(&manifest_dir)
+        .join("assets")
+        .join("emoji")
+        .join("twemoji");
+
+    println!("cargo:rerun-if-changed=build.rs");
+    println!("cargo:rerun-if-changed={}", emoji_dir.display());
+
+    let mut emoji: Vec<(String, String)> = fs::read_dir(&emoji_dir)
+        .expect("emoji directory should be readable")
+        .map(|entry| entry.expect("emoji directory should be readable").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "png"))
+        .map(|path| {
+            let name = path
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .expect("emoji file names should be valid UTF-8")
+                .to_owned();
+
+            (name, path.display().to_string())
+        })
+        .collect();
+    emoji.sort();
+
+    let max_sequence_length = emoji
+        .iter()
+        .map(|(name, _)| name.split('-').count())
+        .max()
+        .unwrap_or_default();
+
+    let mut table = format!("pub const MAX_SEQUENCE_LENGTH: usize = {max_sequence_length};\n\n");
+    table.push_str("static EMOJI: &[(&str, &[u8])] = &[\n");
+    for (name, path) in emoji {
+        table.push_str(&format!("    ({name:?}, include_bytes!({path:?})),\n"));
+    }
+    table.push_str("];\n");
+
+    let out_dir = env::var("OUT_DIR").expect("cargo should set OUT_DIR");
+    fs::write(Path::new(&out_dir).join("emoji.rs"), table).expect("emoji table should be writable");
 }
@@ -12,4 +12,5 @@ macro_rules! host_path_separator {
     };
 }
 
+pub mod emoji;
 pub mod fonts;
@@ -0,0 +1,124 @@
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex, MutexGuard, OnceLock},
+};
+
+use image::RgbaImage;
+
+// defines `MAX_SEQUENCE_LENGTH` and `EMOJI`, generated by the build script from `assets/emoji`
+include!(concat!(env!("OUT_DIR"), "/emoji.rs"));
+
+const VARIATION_SELECTOR_16: char = '\u{FE0F}';
+const COMBINING_KEYCAP: char = '\u{20E3}';
+
+/// Emoji images that have been drawn before, keyed by the name of their sequence. There are only
+/// as many as there are bundled emoji, so nothing is ever evicted.
+static IMAGES: OnceLock<Mutex<HashMap<&'static str, Arc<RgbaImage>>>> = OnceLock::new();
+
+/// A bundled emoji image matched at the start of some text.
+pub struct Emoji {
+    name: &'static str,
+    png_data: &'static [u8],
+    /// How many bytes of the text the emoji covers.
+    pub len: usize,
+    /// Whether the emoji is a sequence or has a variation selector asking for emoji presentation,
+    /// rather than being a lone character that could equally be drawn from a font.
+    pub explicit: bool,
+}
+
+impl Emoji {
+    /// Matches the longest emoji sequence at the start of `text`.
+    pub fn match_start(text: &str) -> Option<Self> {
+        let chars: Vec<(usize, char)> = text.char_indices().take(MAX_SEQUENCE_LENGTH + 1).collect();
+
+        // plain ASCII only starts an emoji as part of a keycap sequence, and is by far the most
+        // common case, so it's ruled out before any lookups
+        if let [(_, first), rest @ ..] = chars.as_slice() {
+            if first.is_ascii()
+                && !matches!(
+                    rest.first(),
+                    Some((_, VARIATION_SELECTOR_16 | COMBINING_KEYCAP))
+                )
+            {
+                return None;
+            }
+        }
+
+        (1..=chars.len().min(MAX_SEQUENCE_LENGTH))
+            .rev()
+            .find_map(|length| {
+                let (name, png_data) = lookup(chars[..length].iter().map(|(_, c)| *c))?;
+
+                // a trailing variation selector belongs to the emoji even where the images omit it
+                let length = match chars.get(length) {
+                    Some((_, VARIATION_SELECTOR_16)) => length + 1,
+                    _ => length,
+                };
+                let len = chars
+                    .get(length)
+                    .map(|(index, _)| *index)
+                    .unwrap_or(text.len());
+
+                Some(Self {
+                    name,
+                    png_data,
+                    len,
+                    explicit: length > 1,
+                })
+            })
+    }
+
+    /// The emoji's image, which is only decoded the first time it's needed.
+    pub fn image(&self) -> Arc<RgbaImage> {
+        if let Some(image) = cached_images().get(self.name) {
+            return Arc::clone(image);
+        }
+
+        let image = image::load_from_memory_with_format(self.png_data, image::ImageFormat::Png)
+            .expect("bundled emoji should decode correctly")
+            .into_rgba8();
+
+        Arc::clone(cached_images().entry(self.name).or_insert(Arc::new(image)))
+    }
+}
+
+fn cached_images() -> MutexGuard<'static, HashMap<&'static str, Arc<RgbaImage>>> {
+    IMAGES
+        .get_or_init(Default::default)
+        .lock()
+        .expect("emoji image cache mutex should not be poisoned")
+}
+
+/// Finds the name and image for a sequence of code points, ignoring variation selectors where the
+/// image set omits them.
+fn lookup(sequence: impl Iterator<Item = char> + Clone) -> Option<(&'static str, &'static [u8])> {
+    let name = |sequence: &mut dyn Iterator<Item = char>| {
+        sequence
+            .map(|c| format!("{:x}", c as u32))
+            .collect::<Vec<String>>()
+            .join("-")
+    };
+
+    let full_name = name(&mut sequence.clone());
+    let stripped_name = name(&mut sequence.filter(|c| *c != VARIATION_SELECTOR_16));
+
+    [full_name, stripped_name].into_iter().find_map(|name| {
+        EMOJI
+            .binary_search_by(|(candidate, _)| (*candidate).cmp(name.as_str()))
+            .ok()
+            .map(|index| EMOJI[index])
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn images_are_only_decoded_once() {
+        let first = Emoji::match_start("\u{1F600}").unwrap().image();
+        let second = Emoji::match_start("\u{1F600} again").unwrap().image();
+
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
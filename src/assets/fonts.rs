@@ -22,7 +22,7 @@ macro_rules! load_font {
             concat!($font_name, "_", $variant, ".ttf"),
         ));
 
-        Font::try_from_bytes(font_data).expect(concat!(
+        ShapingFont::from_static(font_data).expect(concat!(
             "font '",
             concat!($font_name, "_", $variant, ".ttf"),
             "' should parse correctly"
@@ -30,16 +30,315 @@ macro_rules! load_font {
     }};
 }
 
+use std::{
+    fs,
+    path::Path,
+    sync::{Arc, OnceLock},
+};
+
+use anyhow::{Context, Result};
 use rusttype::Font;
+use rustybuzz::Face;
+
+/// A font, along with the tables needed to shape text with it.
+#[derive(Clone)]
+pub struct ShapingFont {
+    pub font: Font<'static>,
+    pub face: Face<'static>,
+}
+
+impl ShapingFont {
+    /// Parses the first font in some font data.
+    pub fn from_static(font_data: &'static [u8]) -> Option<Self> {
+        Some(Self {
+            font: Font::try_from_bytes(font_data)?,
+            face: Face::from_slice(font_data, 0)?,
+        })
+    }
+}
 
 pub struct Lato;
 
 impl Lato {
-    pub fn semibold_italic() -> Font<'static> {
+    pub fn semibold_italic() -> ShapingFont {
         load_font!("lato", "600_italic")
     }
 
-    pub fn bold() -> Font<'static> {
+    pub fn bold() -> ShapingFont {
         load_font!("lato", "700")
     }
+
+    pub fn bold_italic() -> ShapingFont {
+        load_font!("lato", "700_italic")
+    }
+
+    pub fn black() -> ShapingFont {
+        load_font!("lato", "900")
+    }
+
+    pub fn black_italic() -> ShapingFont {
+        load_font!("lato", "900_italic")
+    }
+}
+
+/// Bundled fallback for scripts Lato doesn't cover, such as Cyrillic, Greek, Arabic and Hebrew.
+pub struct DejaVuSans;
+
+impl DejaVuSans {
+    pub fn bold_italic() -> ShapingFont {
+        load_font!("dejavu_sans", "700_italic")
+    }
+
+    pub fn bold() -> ShapingFont {
+        load_font!("dejavu_sans", "700")
+    }
+}
+
+/// Bundled monospace font, for code.
+pub struct DejaVuSansMono;
+
+impl DejaVuSansMono {
+    pub fn bold() -> ShapingFont {
+        load_font!("dejavu_sans_mono", "700")
+    }
+}
+
+/// Fonts loaded at runtime, tried after every bundled font.
+static EXTRA_FONTS: OnceLock<Vec<ShapingFont>> = OnceLock::new();
+
+/// Loads every `.ttf`, `.otf` and `.ttc` file in `dir` in name order, for characters none of the
+/// bundled fonts cover, such as Chinese, Japanese, Korean and Devanagari. Only the first call has
+/// any effect, and it must come before any text is drawn.
+///
+/// Returns how many fonts were loaded.
+pub fn load_extra_fonts(dir: impl AsRef<Path>) -> Result<usize> {
+    let mut paths = fs::read_dir(dir)
+        .context("failed to read fonts directory")?
+        .map(|entry| entry.map(|entry| entry.path()))
+        .collect::<Result<Vec<_>, _>>()
+        .context("failed to read fonts directory")?;
+    paths.sort();
+
+    let mut fonts = Vec::new();
+    for path in paths {
+        let extension = path
+            .extension()
+            .and_then(|extension| extension.to_str())
+            .map(str::to_lowercase);
+        if !matches!(extension.as_deref(), Some("ttf" | "otf" | "ttc")) {
+            continue;
+        }
+
+        let font_data =
+            fs::read(&path).with_context(|| format!("failed to read font `{}`", path.display()))?;
+        // these are kept for the rest of the process's life anyway, so can be borrowed forever
+        let font_data: &'static [u8] = Box::leak(font_data.into_boxed_slice());
+        let font = ShapingFont::from_static(font_data)
+            .with_context(|| format!("font `{}` should parse correctly", path.display()))?;
+
+        fonts.push(font);
+    }
+
+    let count = fonts.len();
+    let _ = EXTRA_FONTS.set(fonts);
+
+    Ok(count)
+}
+
+/// An ordered list of fonts, where each character is drawn with the first font that has a glyph
+/// for it.
+#[derive(Clone)]
+pub struct FontChain {
+    fonts: Arc<Vec<ShapingFont>>,
+}
+
+impl FontChain {
+    /// Builds a chain from the given fonts, followed by any fonts loaded at runtime.
+    pub fn new(fonts: impl IntoIterator<Item = ShapingFont>) -> Self {
+        let fonts = fonts
+            .into_iter()
+            .chain(EXTRA_FONTS.get().into_iter().flatten().cloned())
+            .collect();
+
+        Self {
+            fonts: Arc::new(fonts),
+        }
+    }
+
+    /// Bold text, for quotes.
+    pub fn bold() -> Self {
+        Self::new([Lato::bold(), DejaVuSans::bold()])
+    }
+
+    /// Semibold italic text, for attributions. Scripts without italics in the fallback font, such
+    /// as Arabic, are drawn upright.
+    pub fn semibold_italic() -> Self {
+        Self::new([
+            Lato::semibold_italic(),
+            DejaVuSans::bold_italic(),
+            DejaVuSans::bold(),
+        ])
+    }
+
+    /// The first font in the chain, whose metrics the others are aligned to.
+    pub fn primary(&self) -> &ShapingFont {
+        &self.fonts[0]
+    }
+
+    pub fn get(&self, index: usize) -> &ShapingFont {
+        &self.fonts[index]
+    }
+
+    /// Whether any font in the chain has a glyph for `c`.
+    pub fn covers(&self, c: char) -> bool {
+        self.font_index(c).is_some()
+    }
+
+    /// The index of the first font with a glyph for `c`.
+    pub fn font_index(&self, c: char) -> Option<usize> {
+        self.fonts
+            .iter()
+            .position(|font| font.font.glyph(c).id().0 != 0)
+    }
+
+    /// Splits text into runs that can each be drawn with a single font, pairing each run with the
+    /// index of its font. Characters no font covers are left to the primary font.
+    ///
+    /// Whitespace between runs in different fonts is drawn with the first font that has it, rather
+    /// than the font of the run before it.
+    pub fn runs<'a>(&self, text: &'a str) -> Vec<(usize, &'a str)> {
+        let mut runs: Vec<(usize, &str)> = Vec::new();
+
+        let mut run_start = 0;
+        let mut run_font_index = None;
+        for (index, c) in text.char_indices() {
+            let font_index = match run_font_index {
+                // marks are kept with the character they modify where possible
+                Some(run_font_index)
+                    if is_mark(c) && self.get(run_font_index).font.glyph(c).id().0 != 0 =>
+                {
+                    run_font_index
+                }
+                // spaces between words in the same font are kept in their run, but those between
+                // runs are left to the first font that has them, so the gap isn't sized by
+                // whichever font came before it
+                Some(run_font_index)
+                    if c.is_whitespace()
+                        && self.get(run_font_index).font.glyph(c).id().0 != 0
+                        && text[index..]
+                            .chars()
+                            .find(|c| !c.is_whitespace())
+                            .is_some_and(|next| {
+                                self.font_index(next).unwrap_or(0) == run_font_index
+                            }) =>
+                {
+                    run_font_index
+                }
+                _ => self.font_index(c).unwrap_or(0),
+            };
+
+            if let Some(run_font_index) = run_font_index {
+                if run_font_index != font_index {
+                    runs.push((run_font_index, &text[run_start..index]));
+                    run_start = index;
+                }
+            }
+            run_font_index = Some(font_index);
+        }
+
+        if let Some(run_font_index) = run_font_index {
+            runs.push((run_font_index, &text[run_start..]));
+        }
+
+        runs
+    }
+}
+
+/// The font chains used for each style of text.
+#[derive(Clone)]
+pub struct FontFamily {
+    pub plain: FontChain,
+    /// A heavier weight than the plain text, whatever weight that is.
+    pub bold: FontChain,
+    pub italic: FontChain,
+    pub bold_italic: FontChain,
+    pub code: FontChain,
+}
+
+impl FontFamily {
+    /// Uses the same chain for every style.
+    pub fn uniform(fonts: FontChain) -> Self {
+        Self {
+            plain: fonts.clone(),
+            bold: fonts.clone(),
+            italic: fonts.clone(),
+            bold_italic: fonts.clone(),
+            code: fonts,
+        }
+    }
+
+    /// Bold text with black for emphasis, for quotes.
+    pub fn quote() -> Self {
+        static QUOTE: OnceLock<FontFamily> = OnceLock::new();
+
+        QUOTE
+            .get_or_init(|| Self {
+                plain: FontChain::bold(),
+                bold: FontChain::new([Lato::black(), DejaVuSans::bold()]),
+                italic: FontChain::new([Lato::bold_italic(), DejaVuSans::bold_italic()]),
+                bold_italic: FontChain::new([Lato::black_italic(), DejaVuSans::bold_italic()]),
+                code: FontChain::new([DejaVuSansMono::bold(), DejaVuSans::bold()]),
+            })
+            .clone()
+    }
+
+    /// Semibold italic text in every style, for attributions.
+    pub fn attribution() -> Self {
+        static ATTRIBUTION: OnceLock<FontFamily> = OnceLock::new();
+
+        ATTRIBUTION
+            .get_or_init(|| Self::uniform(FontChain::semibold_italic()))
+            .clone()
+    }
+}
+
+/// Whether `c` is a combining mark or joiner, going by the blocks that hold most of them.
+fn is_mark(c: char) -> bool {
+    matches!(
+        c,
+        '\u{0300}'..='\u{036F}'
+            | '\u{0483}'..='\u{0489}'
+            | '\u{0591}'..='\u{05C7}'
+            | '\u{0610}'..='\u{061A}'
+            | '\u{064B}'..='\u{065F}'
+            | '\u{0670}'
+            | '\u{06D6}'..='\u{06ED}'
+            | '\u{0900}'..='\u{0903}'
+            | '\u{093A}'..='\u{094F}'
+            | '\u{0951}'..='\u{0957}'
+            | '\u{0962}'..='\u{0963}'
+            | '\u{200C}'..='\u{200D}'
+            | '\u{20D0}'..='\u{20FF}'
+            | '\u{FE00}'..='\u{FE0F}'
+            | '\u{FE20}'..='\u{FE2F}'
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn spaces_within_runs_stay_in_them() {
+        let runs = FontChain::bold().runs("مرحبا بالعالم");
+
+        assert_eq!(runs, [(1, "مرحبا بالعالم")]);
+    }
+
+    #[test]
+    fn spaces_between_runs_use_the_primary_font() {
+        let runs = FontChain::bold().runs("say مرحبا بك  twice");
+
+        assert_eq!(runs, [(0, "say "), (1, "مرحبا بك"), (0, "  twice")]);
+    }
 }
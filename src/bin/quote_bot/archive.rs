@@ -0,0 +1,333 @@
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+};
+
+use anyhow::{Context as _, Result};
+use chrono::NaiveDate;
+use rusqlite::{params, types::Value, Connection, OptionalExtension, Row};
+use serenity::{
+    model::prelude::{ChannelId, GuildId, MessageId, UserId},
+    prelude::TypeMapKey,
+};
+
+/// Schema migrations, applied in order. The index of the last applied migration plus one is
+/// tracked in SQLite's `user_version` pragma, so existing entries must never be edited.
+const MIGRATIONS: &[&str] = &[
+    "
+    CREATE TABLE quotes (
+        id INTEGER PRIMARY KEY,
+        text TEXT NOT NULL,
+        author TEXT NOT NULL,
+        date TEXT NOT NULL,
+        requester_id INTEGER NOT NULL,
+        guild_id INTEGER,
+        channel_id INTEGER NOT NULL,
+        message_id INTEGER NOT NULL,
+        photo_id TEXT NOT NULL,
+        created_at TEXT NOT NULL DEFAULT CURRENT_TIMESTAMP
+    );
+
+    CREATE INDEX quotes_guild_id_index ON quotes (guild_id);
+    ",
+    "
+    CREATE VIRTUAL TABLE quotes_fts USING fts5 (
+        text,
+        author,
+        content = 'quotes',
+        content_rowid = 'id'
+    );
+
+    INSERT INTO quotes_fts (rowid, text, author) SELECT id, text, author FROM quotes;
+
+    CREATE TRIGGER quotes_fts_insert AFTER INSERT ON quotes BEGIN
+        INSERT INTO quotes_fts (rowid, text, author) VALUES (new.id, new.text, new.author);
+    END;
+
+    CREATE TRIGGER quotes_fts_delete AFTER DELETE ON quotes BEGIN
+        INSERT INTO quotes_fts (quotes_fts, rowid, text, author)
+            VALUES ('delete', old.id, old.text, old.author);
+    END;
+
+    CREATE TRIGGER quotes_fts_update AFTER UPDATE ON quotes BEGIN
+        INSERT INTO quotes_fts (quotes_fts, rowid, text, author)
+            VALUES ('delete', old.id, old.text, old.author);
+        INSERT INTO quotes_fts (rowid, text, author) VALUES (new.id, new.text, new.author);
+    END;
+    ",
+    "
+    CREATE TABLE guild_settings (
+        guild_id INTEGER PRIMARY KEY,
+        theme TEXT
+    );
+    ",
+];
+
+/// A quote that has been generated and posted.
+#[derive(Debug, Clone)]
+pub struct QuoteRecord {
+    pub text: String,
+    pub author: String,
+    pub date: NaiveDate,
+    pub requester_id: UserId,
+    pub guild_id: Option<GuildId>,
+    pub channel_id: ChannelId,
+    pub message_id: MessageId,
+    pub photo_id: String,
+}
+
+/// Criteria for searching a guild's quotes.
+#[derive(Debug, Clone, Default)]
+pub struct SearchQuery {
+    /// Words that must all appear in the quote text or author.
+    pub terms: Vec<String>,
+    /// Text that must appear in the author's name.
+    pub author: Option<String>,
+    /// Only match quotes dated strictly before this date.
+    pub before: Option<NaiveDate>,
+    /// Only match quotes dated strictly after this date.
+    pub after: Option<NaiveDate>,
+}
+
+impl QuoteRecord {
+    fn from_row(row: &Row) -> rusqlite::Result<Self> {
+        Ok(Self {
+            text: row.get("text")?,
+            author: row.get("author")?,
+            date: row.get("date")?,
+            requester_id: UserId(id_from_sql(row.get("requester_id")?)),
+            guild_id: row
+                .get::<_, Option<i64>>("guild_id")?
+                .map(|guild_id| GuildId(id_from_sql(guild_id))),
+            channel_id: ChannelId(id_from_sql(row.get("channel_id")?)),
+            message_id: MessageId(id_from_sql(row.get("message_id")?)),
+            photo_id: row.get("photo_id")?,
+        })
+    }
+}
+
+#[derive(Clone)]
+pub struct Archive {
+    connection: Arc<Mutex<Connection>>,
+}
+
+impl Archive {
+    pub fn open(path: impl AsRef<Path>) -> Result<Self> {
+        let mut connection = Connection::open(path).context("failed to open database")?;
+
+        migrate(&mut connection).context("failed to migrate database")?;
+
+        Ok(Self {
+            connection: Arc::new(Mutex::new(connection)),
+        })
+    }
+
+    async fn with_connection<T, F>(&self, f: F) -> Result<T>
+    where
+        T: Send + 'static,
+        F: FnOnce(&mut Connection) -> rusqlite::Result<T> + Send + 'static,
+    {
+        let connection = Arc::clone(&self.connection);
+
+        tokio::task::spawn_blocking(move || {
+            let mut connection = connection
+                .lock()
+                .expect("database connection mutex should not be poisoned");
+
+            f(&mut connection)
+        })
+        .await
+        .context("database task panicked")?
+        .context("database query failed")
+    }
+
+    /// Records a posted quote, returning its ID in the archive.
+    pub async fn insert(&self, quote: QuoteRecord) -> Result<i64> {
+        self.with_connection(move |connection| {
+            connection.execute(
+                "INSERT INTO quotes (
+                    text, author, date, requester_id, guild_id, channel_id, message_id, photo_id
+                ) VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+                params![
+                    quote.text,
+                    quote.author,
+                    quote.date,
+                    id_to_sql(quote.requester_id.0),
+                    quote.guild_id.map(|guild_id| id_to_sql(guild_id.0)),
+                    id_to_sql(quote.channel_id.0),
+                    id_to_sql(quote.message_id.0),
+                    quote.photo_id,
+                ],
+            )?;
+
+            Ok(connection.last_insert_rowid())
+        })
+        .await
+    }
+
+    /// Picks a random quote from a guild, optionally only from authors whose name contains
+    /// `author`.
+    pub async fn random(
+        &self,
+        guild_id: GuildId,
+        author: Option<String>,
+    ) -> Result<Option<QuoteRecord>> {
+        self.with_connection(move |connection| {
+            let author_pattern = author.map(|author| format!("%{}%", escape_like(&author)));
+
+            connection
+                .query_row(
+                    "SELECT * FROM quotes
+                    WHERE guild_id = ?1 AND (?2 IS NULL OR author LIKE ?2 ESCAPE '\\')
+                    ORDER BY RANDOM() LIMIT 1",
+                    params![id_to_sql(guild_id.0), author_pattern],
+                    QuoteRecord::from_row,
+                )
+                .optional()
+        })
+        .await
+    }
+
+    /// Searches a guild's quotes, best matches first, returning one page of results along with
+    /// the total number of matches.
+    pub async fn search(
+        &self,
+        guild_id: GuildId,
+        query: SearchQuery,
+        limit: u32,
+        offset: u32,
+    ) -> Result<(Vec<QuoteRecord>, u32)> {
+        self.with_connection(move |connection| {
+            let mut conditions = vec!["quotes.guild_id = ?"];
+            let mut values = vec![Value::Integer(id_to_sql(guild_id.0))];
+
+            let (from, order) = if query.terms.is_empty() {
+                ("quotes", "quotes.date DESC, quotes.id DESC")
+            } else {
+                conditions.push("quotes_fts MATCH ?");
+                values.push(Value::Text(fts_query(&query.terms)));
+
+                (
+                    "quotes_fts JOIN quotes ON quotes.id = quotes_fts.rowid",
+                    "bm25(quotes_fts), quotes.date DESC",
+                )
+            };
+
+            if let Some(author) = query.author {
+                conditions.push("quotes.author LIKE ? ESCAPE '\\'");
+                values.push(Value::Text(format!("%{}%", escape_like(&author))));
+            }
+            if let Some(before) = query.before {
+                conditions.push("quotes.date < ?");
+                values.push(Value::Text(before.to_string()));
+            }
+            if let Some(after) = query.after {
+                conditions.push("quotes.date > ?");
+                values.push(Value::Text(after.to_string()));
+            }
+
+            let condition = conditions.join(" AND ");
+
+            let total_count = connection.query_row(
+                &format!("SELECT COUNT(*) FROM {from} WHERE {condition}"),
+                rusqlite::params_from_iter(&values),
+                |row| row.get(0),
+            )?;
+
+            values.push(Value::Integer(limit.into()));
+            values.push(Value::Integer(offset.into()));
+
+            let quotes = connection
+                .prepare(&format!(
+                    "SELECT quotes.* FROM {from} WHERE {condition} ORDER BY {order} LIMIT ? OFFSET ?"
+                ))?
+                .query_map(rusqlite::params_from_iter(&values), QuoteRecord::from_row)?
+                .collect::<rusqlite::Result<Vec<_>>>()?;
+
+            Ok((quotes, total_count))
+        })
+        .await
+    }
+
+    /// Gets the name of the theme a guild has chosen, if any.
+    pub async fn guild_theme(&self, guild_id: GuildId) -> Result<Option<String>> {
+        self.with_connection(move |connection| {
+            connection
+                .query_row(
+                    "SELECT theme FROM guild_settings WHERE guild_id = ?1",
+                    params![id_to_sql(guild_id.0)],
+                    |row| row.get(0),
+                )
+                .optional()
+                .map(Option::flatten)
+        })
+        .await
+    }
+
+    pub async fn set_guild_theme(&self, guild_id: GuildId, theme: String) -> Result<()> {
+        self.with_connection(move |connection| {
+            connection.execute(
+                "INSERT INTO guild_settings (guild_id, theme) VALUES (?1, ?2)
+                ON CONFLICT (guild_id) DO UPDATE SET theme = excluded.theme",
+                params![id_to_sql(guild_id.0), theme],
+            )?;
+
+            Ok(())
+        })
+        .await
+    }
+}
+
+impl TypeMapKey for Archive {
+    type Value = Archive;
+}
+
+pub async fn get(ctx: &serenity::prelude::Context) -> Archive {
+    ctx.data
+        .read()
+        .await
+        .get::<Archive>()
+        .cloned()
+        .expect("archive should be inserted into client data")
+}
+
+fn migrate(connection: &mut Connection) -> rusqlite::Result<()> {
+    let applied_count: usize = connection.query_row("PRAGMA user_version", [], |row| row.get(0))?;
+
+    for (index, migration) in MIGRATIONS.iter().enumerate().skip(applied_count) {
+        let transaction = connection.transaction()?;
+        transaction.execute_batch(migration)?;
+        transaction.pragma_update(None, "user_version", index + 1)?;
+        transaction.commit()?;
+
+        info!(version = index + 1, "Applied database migration");
+    }
+
+    Ok(())
+}
+
+/// Builds an FTS5 query requiring every term, quoting each so user input can't inject query
+/// syntax.
+fn fts_query(terms: &[String]) -> String {
+    terms
+        .iter()
+        .map(|term| format!("\"{}\"", term.replace('"', "\"\"")))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+fn escape_like(pattern: &str) -> String {
+    pattern
+        .replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+// Discord snowflakes fit in 63 bits, so they round-trip through SQLite's signed integers.
+fn id_to_sql(id: u64) -> i64 {
+    id as i64
+}
+
+fn id_from_sql(id: i64) -> u64 {
+    id as u64
+}
@@ -0,0 +1,49 @@
+use std::sync::Arc;
+
+use anyhow::Result;
+use image::RgbaImage;
+use quote_bot::image_cache::{ImageCache, ImageLocation};
+use serenity::{
+    model::prelude::{User, UserId},
+    prelude::*,
+};
+
+/// Avatars are keyed by the user they belong to and their hash, so a changed avatar is fetched
+/// afresh. Users without one have no hash.
+type AvatarKey = (UserId, Option<String>);
+
+/// Fetches users' avatars, keeping hold of each one so it's only fetched once.
+pub struct AvatarCache {
+    images: ImageCache<AvatarKey>,
+}
+
+impl AvatarCache {
+    const MAX_CACHED_IMAGES: usize = 256;
+    const IMAGE_SIZE: u32 = 128;
+
+    pub fn new() -> Self {
+        Self {
+            images: ImageCache::new(Self::MAX_CACHED_IMAGES),
+        }
+    }
+
+    /// Gets `user`'s avatar, or the default one Discord shows for them if they haven't set one.
+    pub async fn get(&self, user: &User) -> Result<Arc<RgbaImage>> {
+        let url = match &user.avatar {
+            Some(hash) => format!(
+                "https://cdn.discordapp.com/avatars/{}/{hash}.png?size={}",
+                user.id,
+                Self::IMAGE_SIZE
+            ),
+            None => user.default_avatar_url(),
+        };
+
+        self.images
+            .get((user.id, user.avatar.clone()), ImageLocation::Url(url))
+            .await
+    }
+}
+
+impl TypeMapKey for AvatarCache {
+    type Value = Arc<AvatarCache>;
+}
@@ -17,15 +17,17 @@ use std::collections::HashSet;
 
 use serenity::{
     framework::{
-        standard::{buckets::LimitedFor, macros::hook, CommandResult, DispatchError},
+        standard::{macros::hook, CommandResult, DispatchError},
         StandardFramework,
     },
     model::prelude::{Message, UserId},
     prelude::*,
 };
 
+mod archive;
 mod general;
 mod help;
+mod settings;
 
 pub const COMMAND_PREFIX: &str = "q!";
 
@@ -33,13 +35,11 @@ pub async fn framework(owners: HashSet<UserId>) -> StandardFramework {
     StandardFramework::new()
         .configure(|cfg| cfg.prefix(COMMAND_PREFIX).owners(owners))
         .group(&general::GENERAL_GROUP)
+        .group(&archive::ARCHIVE_GROUP)
+        .group(&settings::SETTINGS_GROUP)
         .help(&help::HELP)
         .on_dispatch_error(dispatch_error_hook)
         .after(after_hook)
-        .bucket("unsplash", |b| {
-            b.limit_for(LimitedFor::Global).time_span(3600).limit(50)
-        })
-        .await
 }
 
 #[hook]
@@ -0,0 +1,173 @@
+use anyhow::Context as _;
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::Message,
+    prelude::*,
+};
+
+use crate::{
+    archive::{self, QuoteRecord},
+    generator::{self, Author, QuoteOptions},
+    ratelimit, search, themes,
+};
+
+#[group]
+#[commands(search, random)]
+struct Archive;
+
+#[command]
+#[description("Searches this server's previously generated quotes.")]
+#[usage("[terms] [--author <name>] [--before DD/MM/YYYY] [--after DD/MM/YYYY]")]
+#[example("pizza --author \"Some Guy I Stole From\" --after 01/01/2023")]
+#[only_in(guilds)]
+async fn search(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    instrument_command!("search", msg, {
+        args.trimmed().quoted();
+
+        let guild_id = msg
+            .guild_id
+            .context("search should only be run in guilds")?;
+
+        let query = match search::parse_query(args.iter::<String>().quoted().flatten()) {
+            Ok(query) => query,
+            Err(reply_content) => {
+                msg.reply_ping(ctx, reply_content).await?;
+                return Ok(());
+            }
+        };
+
+        let (quotes, total_count) = archive::get(ctx)
+            .await
+            .search(guild_id, query.clone(), search::PAGE_SIZE, 0)
+            .await?;
+
+        if total_count == 0 {
+            msg.reply_ping(ctx, "No quotes found.").await?;
+            return Ok(());
+        }
+
+        let results_msg = msg
+            .channel_id
+            .send_message(ctx, |m| {
+                m.set_embed(search::results_embed(&quotes, 0, total_count))
+                    .set_components(search::page_buttons(0, total_count))
+            })
+            .await
+            .context("failed to send search results")?;
+
+        search::paginate(
+            ctx,
+            results_msg,
+            msg.author.id,
+            guild_id,
+            query,
+            total_count,
+        )
+        .await?;
+
+        Ok(())
+    })
+}
+
+#[command]
+#[description(
+    "Re-posts a random previously generated quote from this server, optionally re-rendered over a fresh background."
+)]
+#[usage("[author] [--fresh]")]
+#[example("\"Some Guy I Stole From\" --fresh")]
+#[only_in(guilds)]
+async fn random(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    instrument_command!("random", msg, {
+        args.trimmed().quoted();
+
+        let guild_id = msg
+            .guild_id
+            .context("random should only be run in guilds")?;
+
+        let mut fresh = false;
+        let mut author_parts = Vec::new();
+        for arg in args.iter::<String>().quoted().flatten() {
+            if arg == "--fresh" {
+                fresh = true;
+            } else {
+                author_parts.push(arg);
+            }
+        }
+        let author = (!author_parts.is_empty()).then(|| author_parts.join(" "));
+
+        let Some(record) = archive::get(ctx).await.random(guild_id, author).await? else {
+            msg.reply_ping(ctx, "No quotes found.").await?;
+            return Ok(());
+        };
+
+        let original_link = record.message_id.link(record.channel_id, record.guild_id);
+
+        let original_image = if fresh {
+            None
+        } else {
+            original_image(ctx, &record).await
+        };
+
+        let (image_bytes, file_name, truncated) = match original_image {
+            Some((image_bytes, file_name)) => (image_bytes, file_name, false),
+            None => {
+                if ratelimit::take_unsplash(ctx).await.is_err() {
+                    msg.reply_ping(ctx, "Rate limit reached, please try again soon.")
+                        .await?;
+                    return Ok(());
+                }
+
+                let _typing = msg.channel_id.start_typing(&ctx.http)?;
+
+                let options = QuoteOptions {
+                    theme: themes::guild_theme(ctx, Some(guild_id)).await,
+                    ..Default::default()
+                };
+
+                let author = Author::named(record.author.clone());
+                let generated_quote =
+                    generator::generate(ctx, &record.text, &author, record.date, &options).await?;
+
+                (
+                    generated_quote.image_bytes,
+                    generated_quote.file_name,
+                    generated_quote.truncated,
+                )
+            }
+        };
+
+        let mut content = format!("Originally posted at {original_link}");
+        if truncated {
+            content.push('\n');
+            content.push_str(generator::TRUNCATED_NOTICE);
+        }
+
+        msg.channel_id
+            .send_message(ctx, |m| {
+                m.content(content)
+                    .add_file((image_bytes.as_slice(), file_name.as_str()))
+            })
+            .await
+            .context("failed to send quote image")?;
+
+        Ok(())
+    })
+}
+
+/// Downloads the image originally posted for `record`, if it's still around, along with its file
+/// name.
+async fn original_image(ctx: &Context, record: &QuoteRecord) -> Option<(Vec<u8>, String)> {
+    let original_msg = record
+        .channel_id
+        .message(ctx, record.message_id)
+        .await
+        .ok()?;
+
+    let attachment = original_msg.attachments.first()?;
+    let image_bytes = attachment.download().await.ok()?;
+
+    Some((image_bytes, attachment.filename.clone()))
+}
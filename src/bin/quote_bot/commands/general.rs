@@ -1,21 +1,25 @@
-use std::{env, io::Cursor};
-
 use anyhow::Context as _;
 use chrono::{NaiveDate, Utc};
-use image::ImageOutputFormat;
-use quote_bot::{
-    render,
-    unsplash::{
-        GetRandomPhotoOptions, ImgixFitMode, ImgixFormat, ImgixParams, Orientation, UnsplashClient,
-    },
-};
+use quote_bot::render::{AnimationFormat, Filter, Format, Layout};
 use serenity::{
     framework::standard::{
         macros::{command, group},
         Args, CommandResult,
     },
-    model::prelude::Message,
+    http::{HttpError, StatusCode},
+    model::{
+        prelude::{Channel, ChannelId, ChannelType, Guild, Member, Message},
+        Permissions,
+    },
     prelude::*,
+    utils,
+};
+
+use super::COMMAND_PREFIX;
+use crate::{
+    archive::{self, QuoteRecord},
+    generator::{self, QuoteOptions},
+    mentions, ratelimit, themes,
 };
 
 #[group]
@@ -23,63 +27,443 @@ use serenity::{
 struct General;
 
 #[command]
-#[description("Generates an 'inspirational'-style quote image.")]
-#[usage("<quote> <author> [DD/MM/YYYY]")]
+#[description(
+    "Generates an 'inspirational'-style quote image. Reply to a message or pass a message link instead of a quote and author to quote that message."
+)]
+#[usage("<quote> <author> [DD/MM/YYYY] [--layout <classic|editorial|caption|poster>] [--format <landscape|square|portrait|story|wide|banner>] [--animate <gif|webp> [--pan]] [--filters <filter>[:value],...]")]
 #[example("\"Man, I really hope this sentence doesn't get stolen for an example quote.\" \"Some Guy I Stole From\" 29/06/2023")]
-#[min_args(2)]
-#[max_args(3)]
-#[bucket("unsplash")]
+#[example("\"I never said that.\" @someone")]
+#[example("https://discord.com/channels/<server>/<channel>/<message> --layout poster")]
+#[example("\"I never said that.\" @someone --format story")]
+#[example("\"Watch this.\" \"Some Guy I Stole From\" --animate gif --pan")]
+#[example("\"Quiet, please.\" @someone --filters blur:0.01,brightness:0.7,grain")]
 async fn quote(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
     instrument_command!("quote", msg, {
         args.trimmed().quoted();
 
-        let quote: String = args.single()?;
-        let author: String = args.single()?;
+        let (positional_args, mut options) =
+            match parse_quote_args(args.iter::<String>().quoted().flatten()) {
+                Ok(parsed_args) => parsed_args,
+                Err(reply_content) => {
+                    msg.reply_ping(ctx, reply_content).await?;
+                    return Ok(());
+                }
+            };
 
-        let timestamp_raw: Option<String> = args.single().ok();
-        let timestamp = match timestamp_raw {
-            None => Utc::now().date_naive(),
-            Some(s) => NaiveDate::parse_from_str(&s, "%d/%m/%Y")?,
+        if positional_args.len() > 3 {
+            let reply_content = format!(
+                "`{COMMAND_PREFIX}quote` only accepts 3 arguments, but received {}.",
+                positional_args.len()
+            );
+            msg.reply_ping(ctx, reply_content).await?;
+            return Ok(());
+        }
+
+        let message_url = match positional_args.as_slice() {
+            [arg] => utils::parse_message_url(arg),
+            _ => None,
         };
 
-        let _typing = msg.channel_id.start_typing(&ctx.http)?;
+        let (quote, author, timestamp) = if let Some((guild_id, channel_id, message_id)) =
+            message_url
+        {
+            if msg.guild_id != Some(guild_id) {
+                msg.reply_ping(ctx, "You can only quote messages from this server.")
+                    .await?;
+                return Ok(());
+            }
 
-        let unsplash_access_key = env::var("UNSPLASH_KEY")
-            .context("failed to load `UNSPLASH_KEY` environment variable")?;
-        let unsplash_client = UnsplashClient::new(&unsplash_access_key);
-
-        let get_random_photo_options = GetRandomPhotoOptions {
-            collections: Some(String::from("11649432")),
-            orientation: Some(Orientation::Landscape),
-            imgix_params: ImgixParams {
-                height: Some(1080),
-                format: Some(ImgixFormat::Jpg),
-                quality: Some(45),
-                fit_mode: Some(ImgixFitMode::Crop),
-                aspect_ratio: Some(String::from("3:2")),
-                ..Default::default()
-            },
-            ..Default::default()
+            // the message is fetched with the bot's permissions, so without this anyone could
+            // publish messages from channels they can't see themselves
+            let guild = guild_id
+                .to_guild_cached(ctx)
+                .context("server should be cached")?;
+            let member = guild_id
+                .member(ctx, msg.author.id)
+                .await
+                .context("failed to get member quoting a message link")?;
+
+            match channel_permissions(&guild, channel_id, &member) {
+                None => {
+                    msg.reply_ping(ctx, "I couldn't find that message.").await?;
+                    return Ok(());
+                }
+                Some(permissions) if !permissions.contains(READ_PERMISSIONS) => {
+                    msg.reply_ping(ctx, "You can't read messages in that channel.")
+                        .await?;
+                    return Ok(());
+                }
+                Some(_) => {}
+            }
+
+            let linked_msg = match channel_id.message(ctx, message_id).await {
+                Ok(linked_msg) => linked_msg,
+                Err(SerenityError::Http(err)) => {
+                    let reply_content = match *err {
+                        HttpError::UnsuccessfulRequest(ref response)
+                            if response.status_code == StatusCode::FORBIDDEN =>
+                        {
+                            "I can't read messages in that channel."
+                        }
+                        HttpError::UnsuccessfulRequest(ref response)
+                            if response.status_code == StatusCode::NOT_FOUND =>
+                        {
+                            "I couldn't find that message."
+                        }
+                        _ => return Err(SerenityError::Http(err).into()),
+                    };
+
+                    msg.reply_ping(ctx, reply_content).await?;
+                    return Ok(());
+                }
+                Err(err) => return Err(err.into()),
+            };
+
+            generator::message_quote(ctx, &linked_msg, msg.guild_id).await
+        } else if let (true, Some(referenced_msg)) =
+            (positional_args.is_empty(), &msg.referenced_message)
+        {
+            generator::message_quote(ctx, referenced_msg, msg.guild_id).await
+        } else if let [quote, author, timestamp_raw @ ..] = positional_args.as_slice() {
+            let timestamp = match timestamp_raw.first() {
+                None => Utc::now().date_naive(),
+                Some(s) => NaiveDate::parse_from_str(s, generator::DATE_FORMAT)?,
+            };
+
+            let author = mentions::resolve_author(ctx, author, msg.guild_id).await;
+
+            (quote.clone(), author, timestamp)
+        } else {
+            let reply_content = format!(
+                "`{COMMAND_PREFIX}quote` requires a quote and an author, unless it's a reply to or a link to the message to quote."
+            );
+            msg.reply_ping(ctx, reply_content).await?;
+            return Ok(());
         };
 
-        let background_image = unsplash_client
-            .get_random_photo(get_random_photo_options)
-            .await
-            .context("failed to get random background image")?;
+        if quote.trim().is_empty() {
+            msg.reply_ping(ctx, "That message doesn't have any text to quote.")
+                .await?;
+            return Ok(());
+        }
+
+        // only taken once the command's known to be valid, so mistakes don't use up the limit
+        if ratelimit::take_unsplash(ctx).await.is_err() {
+            msg.reply_ping(ctx, "Rate limit reached, please try again soon.")
+                .await?;
+            return Ok(());
+        }
+
+        let _typing = msg.channel_id.start_typing(&ctx.http)?;
 
-        let image = render::render(&background_image, &quote, &author, timestamp);
+        let quote = mentions::resolve(ctx, &quote, msg.guild_id).await;
 
-        let mut image_bytes: Cursor<Vec<u8>> = Cursor::new(Vec::new());
-        image
-            .write_to(&mut image_bytes, ImageOutputFormat::Jpeg(75))
-            .context("failed to encode quote image")?;
-        let image_bytes = image_bytes.into_inner();
+        options.theme = themes::guild_theme(ctx, msg.guild_id).await;
 
-        msg.channel_id
-            .send_message(ctx, |m| m.add_file((image_bytes.as_slice(), "quote.jpg")))
+        let generated_quote =
+            generator::generate(ctx, &quote, &author, timestamp, &options).await?;
+
+        let quote_msg = msg
+            .channel_id
+            .send_message(ctx, |m| {
+                if generated_quote.truncated {
+                    m.content(generator::TRUNCATED_NOTICE);
+                }
+
+                m.add_file((
+                    generated_quote.image_bytes.as_slice(),
+                    generated_quote.file_name.as_str(),
+                ))
+            })
             .await
             .context("failed to send quote image")?;
 
+        archive::get(ctx)
+            .await
+            .insert(QuoteRecord {
+                text: quote,
+                author: author.name,
+                date: timestamp,
+                requester_id: msg.author.id,
+                guild_id: msg.guild_id,
+                channel_id: quote_msg.channel_id,
+                message_id: quote_msg.id,
+                photo_id: generated_quote.photo_id,
+            })
+            .await
+            .context("failed to archive quote")?;
+
         Ok(())
     })
 }
+
+/// What someone needs in a channel to quote a message from it by its link.
+const READ_PERMISSIONS: Permissions =
+    Permissions::VIEW_CHANNEL.union(Permissions::READ_MESSAGE_HISTORY);
+
+/// The permissions `member` has in the channel or thread `channel_id` of `guild`, or `None` if
+/// there's no such channel. Threads take their permissions from the channel they're in, except
+/// that private ones can only be seen by moderators here, as their members aren't known.
+fn channel_permissions(
+    guild: &Guild,
+    channel_id: ChannelId,
+    member: &Member,
+) -> Option<Permissions> {
+    if let Some(Channel::Guild(channel)) = guild.channels.get(&channel_id) {
+        return guild.user_permissions_in(channel, member).ok();
+    }
+
+    let thread = guild
+        .threads
+        .iter()
+        .find(|thread| thread.id == channel_id)?;
+    let Some(Channel::Guild(parent)) = guild.channels.get(&thread.parent_id?) else {
+        return None;
+    };
+
+    let mut permissions = guild.user_permissions_in(parent, member).ok()?;
+    if thread.kind == ChannelType::PrivateThread
+        && !permissions.contains(Permissions::MANAGE_THREADS)
+    {
+        permissions.remove(Permissions::VIEW_CHANNEL);
+    }
+
+    Some(permissions)
+}
+
+/// Separates `--option value` pairs and `--flag`s from positional arguments.
+///
+/// Errors are messages suitable for showing to the user.
+fn parse_quote_args(
+    args: impl IntoIterator<Item = String>,
+) -> Result<(Vec<String>, QuoteOptions), String> {
+    let mut positional_args = Vec::new();
+    let mut options = QuoteOptions::default();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(option) = arg.strip_prefix("--") else {
+            positional_args.push(arg);
+            continue;
+        };
+
+        if option == "pan" {
+            options.animation_options.ken_burns = true;
+            continue;
+        }
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("`--{option}` needs a value."))?;
+
+        match option {
+            "layout" => {
+                options.layout = value.parse().map_err(|_| {
+                    let layout_names = Layout::ALL.map(|layout| format!("`{layout}`")).join(", ");
+
+                    format!("Unknown layout `{value}`. Available layouts are {layout_names}.")
+                })?;
+            }
+            "format" => {
+                options.format = value.parse().map_err(|_| {
+                    let format_names = Format::ALL.map(|format| format!("`{format}`")).join(", ");
+
+                    format!("Unknown format `{value}`. Available formats are {format_names}.")
+                })?;
+            }
+            "animate" => {
+                options.animation = Some(value.parse().map_err(|_| {
+                    let format_names = AnimationFormat::ALL
+                        .map(|format| format!("`{format}`"))
+                        .join(", ");
+
+                    format!(
+                        "Unknown animation format `{value}`. Available formats are {format_names}."
+                    )
+                })?);
+            }
+            "filters" => {
+                options.filters = Some(
+                    Filter::parse_list(&value).map_err(|err| format!("Invalid filters: {err}."))?,
+                );
+            }
+            _ => return Err(format!("Unknown option `--{option}`.")),
+        }
+    }
+
+    if options.animation_options.ken_burns && options.animation.is_none() {
+        return Err(String::from("`--pan` only works with `--animate`."));
+    }
+
+    Ok((positional_args, options))
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const GUILD_ID: u64 = 1;
+    const MODERATOR_ROLE_ID: u64 = 2;
+    const PUBLIC_CHANNEL_ID: u64 = 10;
+    const PRIVATE_CHANNEL_ID: u64 = 11;
+    const MODERATOR_CHANNEL_ID: u64 = 12;
+    const THREAD_ID: u64 = 20;
+    const PRIVATE_THREAD_ID: u64 = 21;
+
+    const EVERYONE_PERMISSIONS: Permissions = READ_PERMISSIONS.union(Permissions::SEND_MESSAGES);
+
+    fn channel(
+        id: u64,
+        kind: u8,
+        parent_id: Option<u64>,
+        denied_to_everyone: Permissions,
+    ) -> Value {
+        json!({
+            "id": id.to_string(),
+            "type": kind,
+            "guild_id": GUILD_ID.to_string(),
+            "parent_id": parent_id.map(|id| id.to_string()),
+            "name": format!("channel-{id}"),
+            "position": 0,
+            "permission_overwrites": [
+                {
+                    "id": GUILD_ID.to_string(),
+                    "type": 0,
+                    "allow": "0",
+                    "deny": denied_to_everyone.bits().to_string(),
+                },
+                {
+                    "id": MODERATOR_ROLE_ID.to_string(),
+                    "type": 0,
+                    "allow": READ_PERMISSIONS.bits().to_string(),
+                    "deny": "0",
+                },
+            ],
+        })
+    }
+
+    fn role(id: u64, name: &str, permissions: Permissions) -> Value {
+        json!({
+            "id": id.to_string(),
+            "guild_id": GUILD_ID.to_string(),
+            "name": name,
+            "permissions": permissions.bits().to_string(),
+            "position": id,
+            "color": 0,
+            "hoist": false,
+            "managed": false,
+            "mentionable": false,
+        })
+    }
+
+    /// A server with a public channel, a channel hidden from everyone and one only moderators can
+    /// see, plus a public and a private thread in the public channel.
+    fn guild() -> Guild {
+        serde_json::from_value(json!({
+            "id": GUILD_ID.to_string(),
+            "name": "Server",
+            "icon": null,
+            "owner_id": "100",
+            "afk_channel_id": null,
+            "afk_timeout": 300,
+            "default_message_notifications": 0,
+            "explicit_content_filter": 0,
+            "emojis": [],
+            "features": [],
+            "joined_at": "2024-01-01T00:00:00Z",
+            "large": false,
+            "member_count": 0,
+            "members": [],
+            "mfa_level": 0,
+            "presences": [],
+            "roles": [
+                role(GUILD_ID, "@everyone", EVERYONE_PERMISSIONS),
+                role(
+                    MODERATOR_ROLE_ID,
+                    "Moderator",
+                    EVERYONE_PERMISSIONS | Permissions::MANAGE_THREADS,
+                ),
+            ],
+            "splash": null,
+            "stickers": [],
+            "system_channel_id": null,
+            "system_channel_flags": 0,
+            "verification_level": 0,
+            "voice_states": [],
+            "description": null,
+            "banner": null,
+            "vanity_url_code": null,
+            "preferred_locale": "en-GB",
+            "nsfw_level": 0,
+            "channels": [
+                channel(PUBLIC_CHANNEL_ID, 0, None, Permissions::empty()),
+                channel(PRIVATE_CHANNEL_ID, 0, None, Permissions::VIEW_CHANNEL),
+                channel(MODERATOR_CHANNEL_ID, 0, None, Permissions::READ_MESSAGE_HISTORY),
+            ],
+            "threads": [
+                channel(THREAD_ID, 11, Some(PUBLIC_CHANNEL_ID), Permissions::empty()),
+                channel(PRIVATE_THREAD_ID, 12, Some(PUBLIC_CHANNEL_ID), Permissions::empty()),
+            ],
+        }))
+        .expect("test server should deserialise")
+    }
+
+    fn member(role_ids: &[u64]) -> Member {
+        serde_json::from_value(json!({
+            "guild_id": GUILD_ID.to_string(),
+            "user": {
+                "id": "200",
+                "username": "someone",
+                "discriminator": "0001",
+                "avatar": null,
+            },
+            "roles": role_ids.iter().map(u64::to_string).collect::<Vec<_>>(),
+            "joined_at": "2024-01-01T00:00:00Z",
+            "deaf": false,
+            "mute": false,
+        }))
+        .expect("test member should deserialise")
+    }
+
+    fn can_read(channel_id: u64, member: &Member) -> bool {
+        channel_permissions(&guild(), ChannelId(channel_id), member)
+            .expect("channel should exist")
+            .contains(READ_PERMISSIONS)
+    }
+
+    #[test]
+    fn members_can_quote_channels_they_can_read() {
+        let member = member(&[]);
+
+        assert!(can_read(PUBLIC_CHANNEL_ID, &member));
+        assert!(can_read(THREAD_ID, &member));
+    }
+
+    #[test]
+    fn members_cant_quote_channels_hidden_from_them() {
+        let member = member(&[]);
+
+        assert!(!can_read(PRIVATE_CHANNEL_ID, &member));
+        assert!(!can_read(MODERATOR_CHANNEL_ID, &member));
+        assert!(!can_read(PRIVATE_THREAD_ID, &member));
+    }
+
+    #[test]
+    fn roles_can_let_members_quote_hidden_channels() {
+        let moderator = member(&[MODERATOR_ROLE_ID]);
+
+        assert!(can_read(PRIVATE_CHANNEL_ID, &moderator));
+        assert!(can_read(MODERATOR_CHANNEL_ID, &moderator));
+        assert!(can_read(PRIVATE_THREAD_ID, &moderator));
+    }
+
+    #[test]
+    fn unknown_channels_have_no_permissions() {
+        assert_eq!(
+            channel_permissions(&guild(), ChannelId(999), &member(&[])),
+            None
+        );
+    }
+}
@@ -0,0 +1,77 @@
+use anyhow::Context as _;
+use serenity::{
+    framework::standard::{
+        macros::{command, group},
+        Args, CommandResult,
+    },
+    model::prelude::Message,
+    prelude::*,
+};
+
+use super::COMMAND_PREFIX;
+use crate::{archive, themes};
+
+#[group]
+#[commands(themes, theme)]
+struct Settings;
+
+#[command]
+#[description("Lists the themes quotes can be rendered with.")]
+async fn themes(ctx: &Context, msg: &Message) -> CommandResult {
+    instrument_command!("themes", msg, {
+        let themes = themes::get(ctx).await;
+
+        let theme_names = themes
+            .names()
+            .map(|name| format!("`{name}`"))
+            .collect::<Vec<String>>();
+
+        let reply_content = if theme_names.is_empty() {
+            String::from("No themes are available.")
+        } else {
+            format!(
+                "Available themes are {}. Server admins can pick one with `{COMMAND_PREFIX}theme <name>`.",
+                theme_names.join(", ")
+            )
+        };
+        msg.reply_ping(ctx, reply_content).await?;
+
+        Ok(())
+    })
+}
+
+#[command]
+#[description("Sets the theme quotes in this server are rendered with.")]
+#[usage("<name>")]
+#[example("dark")]
+#[num_args(1)]
+#[only_in(guilds)]
+#[required_permissions("MANAGE_GUILD")]
+async fn theme(ctx: &Context, msg: &Message, mut args: Args) -> CommandResult {
+    instrument_command!("theme", msg, {
+        let guild_id = msg.guild_id.context("theme should only be run in guilds")?;
+
+        let theme_name = args.trimmed().single::<String>()?.to_lowercase();
+
+        if themes::get(ctx).await.get(&theme_name).is_none() {
+            let reply_content = format!(
+                "Unknown theme `{theme_name}`. Use `{COMMAND_PREFIX}themes` to see the available themes."
+            );
+            msg.reply_ping(ctx, reply_content).await?;
+            return Ok(());
+        }
+
+        archive::get(ctx)
+            .await
+            .set_guild_theme(guild_id, theme_name.clone())
+            .await?;
+
+        msg.reply_ping(
+            ctx,
+            format!("Quotes in this server will now use the `{theme_name}` theme."),
+        )
+        .await?;
+
+        Ok(())
+    })
+}
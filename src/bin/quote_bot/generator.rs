@@ -0,0 +1,256 @@
+use std::{cmp::Ordering, env, io::Cursor, sync::Arc};
+
+use anyhow::{Context as _, Result};
+use chrono::NaiveDate;
+use image::ImageOutputFormat;
+use quote_bot::{
+    custom_emoji::{CustomEmojiCache, CustomEmojiSource},
+    render::{
+        self, AnimationFormat, AnimationOptions, Filter, Format, Layout, RenderOptions, Theme,
+    },
+    unsplash::{
+        GetRandomPhotoOptions, ImgixFitMode, ImgixFormat, ImgixParams, Orientation, UnsplashClient,
+    },
+};
+use serenity::{
+    model::prelude::{GuildId, Message, User},
+    prelude::*,
+};
+
+use crate::avatars::AvatarCache;
+
+pub const DATE_FORMAT: &str = "%d/%m/%Y";
+
+/// The largest file Discord accepts in servers without boosts, in bytes.
+const MAX_UPLOAD_BYTES: usize = 10 * 1000 * 1000;
+
+/// Sent alongside quotes that had to be cut short to fit on the image.
+pub const TRUNCATED_NOTICE: &str =
+    "That quote was too long to fit on the image, even at the smallest text size, so it's been cut short.";
+
+/// Per-request choices about how a quote is rendered.
+#[derive(Debug, Clone, Default)]
+pub struct QuoteOptions {
+    pub format: Format,
+    pub layout: Layout,
+    pub theme: Theme,
+    /// Filters to apply to the background instead of the theme's own, if any are given.
+    pub filters: Option<Vec<Filter>>,
+    /// The format to animate the quote in, if it's animated at all.
+    pub animation: Option<AnimationFormat>,
+    pub animation_options: AnimationOptions,
+}
+
+/// Cache of the custom emoji drawn onto quotes, shared by every command.
+pub struct EmojiCache;
+
+impl EmojiCache {
+    /// Fetches emoji from Discord, or from `CUSTOM_EMOJI_DIR` if it's set.
+    pub fn cache() -> CustomEmojiCache {
+        let source = match env::var("CUSTOM_EMOJI_DIR") {
+            Ok(dir) if !dir.is_empty() => CustomEmojiSource::Directory(dir.into()),
+            _ => CustomEmojiSource::Discord,
+        };
+
+        CustomEmojiCache::new(source)
+    }
+}
+
+impl TypeMapKey for EmojiCache {
+    type Value = Arc<CustomEmojiCache>;
+}
+
+/// Who a quote is attributed to.
+#[derive(Debug, Clone)]
+pub struct Author {
+    pub name: String,
+    /// The Discord user the quote is attributed to, if it's one, whose avatar is drawn beside the
+    /// attribution.
+    pub user: Option<User>,
+}
+
+impl Author {
+    /// An author who isn't known to be a Discord user.
+    pub fn named(name: impl Into<String>) -> Self {
+        Self {
+            name: name.into(),
+            user: None,
+        }
+    }
+}
+
+pub struct GeneratedQuote {
+    /// The rendered quote, encoded as a JPEG, or as a GIF or WebP if it's animated.
+    pub image_bytes: Vec<u8>,
+    /// What to call the image when it's uploaded, with the extension for its format.
+    pub file_name: String,
+    /// The Unsplash ID of the background photo.
+    pub photo_id: String,
+    /// Whether the quote had to be cut short to fit.
+    pub truncated: bool,
+}
+
+/// Fetches a background image and renders a quote onto it.
+pub async fn generate(
+    ctx: &Context,
+    quote: &str,
+    author: &Author,
+    timestamp: NaiveDate,
+    options: &QuoteOptions,
+) -> Result<GeneratedQuote> {
+    let unsplash_access_key =
+        env::var("UNSPLASH_KEY").context("failed to load `UNSPLASH_KEY` environment variable")?;
+    let unsplash_client = UnsplashClient::new(&unsplash_access_key);
+
+    let dimensions = options.format.dimensions();
+    let aspect_ratio = options.format.aspect_ratio();
+    let orientation = match dimensions.0.cmp(&dimensions.1) {
+        Ordering::Greater => Orientation::Landscape,
+        Ordering::Less => Orientation::Portrait,
+        Ordering::Equal => Orientation::Squarish,
+    };
+
+    let get_random_photo_options = GetRandomPhotoOptions {
+        collections: Some(String::from("11649432")),
+        orientation: Some(orientation),
+        imgix_params: ImgixParams {
+            width: Some(dimensions.0),
+            height: Some(dimensions.1),
+            format: Some(ImgixFormat::Jpg),
+            quality: Some(45),
+            fit_mode: Some(ImgixFitMode::Crop),
+            aspect_ratio: Some(format!("{}:{}", aspect_ratio.0, aspect_ratio.1)),
+        },
+        ..Default::default()
+    };
+
+    let background_photo = unsplash_client
+        .get_random_photo(get_random_photo_options)
+        .await
+        .context("failed to get random background image")?;
+
+    let (emoji_cache, avatar_cache) = {
+        let data = ctx.data.read().await;
+
+        (
+            data.get::<EmojiCache>()
+                .cloned()
+                .expect("emoji cache should be inserted into client data"),
+            data.get::<AvatarCache>()
+                .cloned()
+                .expect("avatar cache should be inserted into client data"),
+        )
+    };
+    let custom_emoji = emoji_cache.resolve(quote).await;
+
+    let avatar = match &author.user {
+        Some(user) => match avatar_cache.get(user).await {
+            Ok(avatar) => Some(avatar),
+            Err(err) => {
+                warn!(user_id = user.id.0, "Failed to fetch avatar: {err:?}");
+                None
+            }
+        },
+        None => None,
+    };
+
+    let mut theme = options.theme.clone();
+    if let Some(filters) = &options.filters {
+        theme.filters = filters.clone();
+    }
+
+    let render_options = RenderOptions {
+        format: options.format,
+        layout: options.layout,
+        theme,
+    };
+
+    // rendering and encoding take long enough that they'd hold up everything else on the runtime
+    let photo_id = background_photo.id;
+    let background = background_photo.image;
+    let quote = quote.to_owned();
+    let author_name = author.name.clone();
+    let animation = options.animation;
+    let animation_options = options.animation_options;
+
+    let (image_bytes, file_name, truncated) = tokio::task::spawn_blocking(move || {
+        if let Some(animation_format) = animation {
+            let rendered_animation = render::render_animated(
+                &background,
+                &quote,
+                &author_name,
+                timestamp,
+                &custom_emoji,
+                avatar.as_deref(),
+                &render_options,
+                &animation_options,
+            );
+
+            let image_bytes = render::encode_animation(
+                &rendered_animation.frames,
+                animation_format,
+                MAX_UPLOAD_BYTES,
+            )
+            .context("failed to encode animated quote")?;
+
+            return Ok((
+                image_bytes,
+                format!("quote.{}", animation_format.extension()),
+                rendered_animation.truncated,
+            ));
+        }
+
+        let rendered_card = render::render(
+            &background,
+            &quote,
+            &author_name,
+            timestamp,
+            &custom_emoji,
+            avatar.as_deref(),
+            &render_options,
+        );
+
+        let mut image_bytes: Cursor<Vec<u8>> = Cursor::new(Vec::new());
+        rendered_card
+            .image
+            .write_to(&mut image_bytes, ImageOutputFormat::Jpeg(75))
+            .context("failed to encode quote image")?;
+
+        Ok::<_, anyhow::Error>((
+            image_bytes.into_inner(),
+            String::from("quote.jpg"),
+            rendered_card.truncated,
+        ))
+    })
+    .await
+    .context("quote rendering task failed")??;
+
+    Ok(GeneratedQuote {
+        image_bytes,
+        file_name,
+        photo_id,
+        truncated,
+    })
+}
+
+/// Derives the quote text, author and date from an existing message.
+///
+/// The author is attributed by their nickname in `guild_id` where they have one.
+pub async fn message_quote(
+    ctx: &Context,
+    msg: &Message,
+    guild_id: Option<GuildId>,
+) -> (String, Author, NaiveDate) {
+    let name = match msg.guild_id.or(guild_id) {
+        Some(guild_id) => msg.author.nick_in(ctx, guild_id).await,
+        None => None,
+    }
+    .unwrap_or_else(|| msg.author.name.clone());
+
+    let author = Author {
+        name,
+        user: Some(msg.author.clone()),
+    };
+
+    (msg.content.clone(), author, msg.timestamp.date_naive())
+}
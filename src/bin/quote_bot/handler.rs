@@ -1,15 +1,29 @@
-use serenity::{async_trait, model::prelude::Ready, prelude::*};
+use serenity::{
+    async_trait,
+    model::{application::interaction::Interaction, prelude::Ready},
+    prelude::*,
+};
+
+use crate::interactions;
 
 pub struct Handler;
 
 #[async_trait]
 impl EventHandler for Handler {
     #[instrument(level = "error", skip_all)]
-    async fn ready(&self, _ctx: Context, bot: Ready) {
+    async fn ready(&self, ctx: Context, bot: Ready) {
         info!(
             id = u64::from(bot.user.id),
             "Logged in as {}",
             bot.user.tag()
         );
+
+        if let Err(err) = interactions::register(&ctx).await {
+            error!("Failed to register application commands: {err:?}");
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        interactions::dispatch(&ctx, interaction).await;
     }
 }
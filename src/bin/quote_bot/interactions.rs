@@ -0,0 +1,190 @@
+macro_rules! instrument_interaction {
+    ($name:expr, $interaction:ident, $body:block) => {{
+        use serenity::model::prelude::{ChannelId, InteractionId};
+        use tracing::Instrument;
+
+        async move { $body }
+            .instrument(error_span!(
+                $name,
+                interaction_id = <u64 as From<InteractionId>>::from($interaction.id),
+                channel_id = <u64 as From<ChannelId>>::from($interaction.channel_id)
+            ))
+            .await
+    }};
+}
+
+use anyhow::{Context as _, Result};
+use chrono::NaiveDate;
+use serenity::{
+    model::{
+        application::interaction::{
+            application_command::{ApplicationCommandInteraction, CommandDataOptionValue},
+            Interaction, InteractionResponseType,
+        },
+        prelude::command::Command,
+    },
+    prelude::*,
+};
+
+use crate::{
+    archive::{self, QuoteRecord},
+    generator::{self, Author, QuoteOptions},
+    mentions, ratelimit,
+};
+
+mod quote;
+mod quote_message;
+mod search;
+
+pub async fn register(ctx: &Context) -> Result<()> {
+    Command::set_global_application_commands(&ctx.http, |commands| {
+        commands
+            .create_application_command(quote::register)
+            .create_application_command(quote_message::register)
+            .create_application_command(search::register)
+    })
+    .await
+    .context("failed to register application commands")?;
+
+    Ok(())
+}
+
+pub async fn dispatch(ctx: &Context, interaction: Interaction) {
+    if let Interaction::ApplicationCommand(command) = interaction {
+        let command_name = command.data.name.as_str();
+
+        let result = match command_name {
+            "quote" => quote::run(ctx, &command).await,
+            quote_message::NAME => quote_message::run(ctx, &command).await,
+            "search" => search::run(ctx, &command).await,
+            _ => {
+                warn!(
+                    command = command_name,
+                    "Received unknown application command"
+                );
+                return;
+            }
+        };
+
+        if let Err(err) = result {
+            error!(command = command_name, "Command error occurred: {err:?}");
+        }
+    }
+}
+
+fn string_option<'a>(command: &'a ApplicationCommandInteraction, name: &str) -> Option<&'a str> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match &option.resolved {
+            Some(CommandDataOptionValue::String(value)) => Some(value.as_str()),
+            _ => None,
+        })
+}
+
+fn bool_option(command: &ApplicationCommandInteraction, name: &str) -> Option<bool> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| match &option.resolved {
+            Some(CommandDataOptionValue::Boolean(value)) => Some(*value),
+            _ => None,
+        })
+}
+
+async fn reply_ephemeral(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    content: &str,
+) -> Result<()> {
+    command
+        .create_interaction_response(&ctx.http, |r| {
+            r.kind(InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| d.content(content).ephemeral(true))
+        })
+        .await
+        .context("failed to send interaction response")?;
+
+    Ok(())
+}
+
+/// Takes a use of the Unsplash rate limit and defers the response to `command`, returning whether
+/// a quote can be generated. The user's told if they've reached the limit.
+///
+/// This should be called once the command's options are known to be valid, so mistakes don't use
+/// up the limit, but before anything slow, so Discord's deadline for responding isn't missed.
+async fn start_quote(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<bool> {
+    if ratelimit::take_unsplash(ctx).await.is_err() {
+        reply_ephemeral(ctx, command, "Rate limit reached, please try again soon.").await?;
+        return Ok(false);
+    }
+
+    command
+        .defer(&ctx.http)
+        .await
+        .context("failed to defer interaction response")?;
+
+    Ok(true)
+}
+
+/// Generates a quote image and sends it as the response to `command`, which must have been
+/// started with [`start_quote`].
+async fn respond_with_quote(
+    ctx: &Context,
+    command: &ApplicationCommandInteraction,
+    quote: &str,
+    author: &Author,
+    timestamp: NaiveDate,
+    options: &QuoteOptions,
+) -> Result<()> {
+    let quote = mentions::resolve(ctx, quote, command.guild_id).await;
+
+    let generated_quote = match generator::generate(ctx, &quote, author, timestamp, options).await {
+        Ok(generated_quote) => generated_quote,
+        Err(err) => {
+            command
+                .create_followup_message(&ctx.http, |m| {
+                    m.content("Something went wrong while generating your quote.")
+                })
+                .await
+                .context("failed to send error followup")?;
+
+            return Err(err);
+        }
+    };
+
+    let quote_msg = command
+        .create_followup_message(&ctx.http, |m| {
+            if generated_quote.truncated {
+                m.content(generator::TRUNCATED_NOTICE);
+            }
+
+            m.add_file((
+                generated_quote.image_bytes.as_slice(),
+                generated_quote.file_name.as_str(),
+            ))
+        })
+        .await
+        .context("failed to send quote image")?;
+
+    archive::get(ctx)
+        .await
+        .insert(QuoteRecord {
+            text: quote,
+            author: author.name.clone(),
+            date: timestamp,
+            requester_id: command.user.id,
+            guild_id: command.guild_id,
+            channel_id: quote_msg.channel_id,
+            message_id: quote_msg.id,
+            photo_id: generated_quote.photo_id,
+        })
+        .await
+        .context("failed to archive quote")?;
+
+    Ok(())
+}
@@ -0,0 +1,159 @@
+use anyhow::{Context as _, Result};
+use chrono::{NaiveDate, Utc};
+use quote_bot::render::{AnimationFormat, AnimationOptions, Filter, Format, Layout};
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{
+        application::interaction::application_command::ApplicationCommandInteraction,
+        prelude::command::CommandOptionType,
+    },
+    prelude::*,
+};
+
+use super::{bool_option, reply_ephemeral, respond_with_quote, start_quote, string_option};
+use crate::{
+    generator::{self, QuoteOptions},
+    mentions, themes,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("quote")
+        .description("Generates an 'inspirational'-style quote image.")
+        .create_option(|option| {
+            option
+                .name("text")
+                .description("The quote itself.")
+                .kind(CommandOptionType::String)
+                .required(true)
+        })
+        .create_option(|option| {
+            option
+                .name("author")
+                .description("Who said it. Mention someone to use their display name.")
+                .kind(CommandOptionType::String)
+                .required(true)
+        })
+        .create_option(|option| {
+            option
+                .name("date")
+                .description("When it was said, as DD/MM/YYYY. Defaults to today.")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("layout")
+                .description("How the quote is arranged on the card.")
+                .kind(CommandOptionType::String)
+                .required(false);
+
+            for layout in Layout::ALL {
+                option.add_string_choice(layout, layout);
+            }
+
+            option
+        })
+        .create_option(|option| {
+            option
+                .name("format")
+                .description("The shape of the image.")
+                .kind(CommandOptionType::String)
+                .required(false);
+
+            for format in Format::ALL {
+                option.add_string_choice(format, format);
+            }
+
+            option
+        })
+        .create_option(|option| {
+            option
+                .name("animate")
+                .description("Types the quote out in an animated image of this format.")
+                .kind(CommandOptionType::String)
+                .required(false);
+
+            for format in AnimationFormat::ALL {
+                option.add_string_choice(format, format);
+            }
+
+            option
+        })
+        .create_option(|option| {
+            option
+                .name("pan")
+                .description("Slowly pans across the background of an animated quote.")
+                .kind(CommandOptionType::Boolean)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("filters")
+                .description(
+                    "Background effects to use instead of the theme's, e.g. blur:0.01,brightness:0.7,grain.",
+                )
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    instrument_interaction!("quote", command, {
+        let quote = string_option(command, "text").context("missing `text` option")?;
+        let author = string_option(command, "author").context("missing `author` option")?;
+
+        let timestamp = match string_option(command, "date") {
+            None => Utc::now().date_naive(),
+            Some(s) => match NaiveDate::parse_from_str(s, generator::DATE_FORMAT) {
+                Ok(timestamp) => timestamp,
+                Err(_) => {
+                    return reply_ephemeral(ctx, command, "Dates must be in DD/MM/YYYY format.")
+                        .await;
+                }
+            },
+        };
+
+        let filters = match string_option(command, "filters").map(Filter::parse_list) {
+            None => None,
+            Some(Ok(filters)) => Some(filters),
+            Some(Err(err)) => {
+                return reply_ephemeral(ctx, command, &format!("Invalid filters: {err}.")).await;
+            }
+        };
+
+        let format = string_option(command, "format")
+            .map(str::parse)
+            .transpose()
+            .context("invalid `format` option")?
+            .unwrap_or_default();
+        let layout = string_option(command, "layout")
+            .map(str::parse)
+            .transpose()
+            .context("invalid `layout` option")?
+            .unwrap_or_default();
+        let animation = string_option(command, "animate")
+            .map(str::parse)
+            .transpose()
+            .context("invalid `animate` option")?;
+
+        if !start_quote(ctx, command).await? {
+            return Ok(());
+        }
+
+        let options = QuoteOptions {
+            format,
+            layout,
+            theme: themes::guild_theme(ctx, command.guild_id).await,
+            filters,
+            animation,
+            animation_options: AnimationOptions {
+                ken_burns: bool_option(command, "pan").unwrap_or_default(),
+            },
+        };
+
+        let author = mentions::resolve_author(ctx, author, command.guild_id).await;
+
+        respond_with_quote(ctx, command, quote, &author, timestamp, &options).await
+    })
+}
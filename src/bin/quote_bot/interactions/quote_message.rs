@@ -0,0 +1,51 @@
+use anyhow::{bail, Result};
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{
+        application::interaction::application_command::{
+            ApplicationCommandInteraction, ResolvedTarget,
+        },
+        prelude::command::CommandType,
+    },
+    prelude::*,
+};
+
+use super::{reply_ephemeral, respond_with_quote, start_quote};
+use crate::{
+    generator::{self, QuoteOptions},
+    themes,
+};
+
+pub const NAME: &str = "Quote this message";
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command.name(NAME).kind(CommandType::Message)
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    instrument_interaction!("quote_message", command, {
+        let message = match command.data.target() {
+            Some(ResolvedTarget::Message(message)) => message,
+            _ => bail!("missing target message"),
+        };
+
+        if message.content.trim().is_empty() {
+            return reply_ephemeral(ctx, command, "That message doesn't have any text to quote.")
+                .await;
+        }
+
+        if !start_quote(ctx, command).await? {
+            return Ok(());
+        }
+
+        let (quote, author, timestamp) =
+            generator::message_quote(ctx, &message, command.guild_id).await;
+
+        let options = QuoteOptions {
+            theme: themes::guild_theme(ctx, command.guild_id).await,
+            ..Default::default()
+        };
+
+        respond_with_quote(ctx, command, &quote, &author, timestamp, &options).await
+    })
+}
@@ -0,0 +1,115 @@
+use anyhow::{Context as _, Result};
+use serenity::{
+    builder::CreateApplicationCommand,
+    model::{
+        application::interaction::{
+            application_command::ApplicationCommandInteraction, InteractionResponseType,
+        },
+        prelude::command::CommandOptionType,
+    },
+    prelude::*,
+};
+
+use super::{reply_ephemeral, string_option};
+use crate::{
+    archive::{self, SearchQuery},
+    search,
+};
+
+pub fn register(command: &mut CreateApplicationCommand) -> &mut CreateApplicationCommand {
+    command
+        .name("search")
+        .description("Searches this server's previously generated quotes.")
+        .dm_permission(false)
+        .create_option(|option| {
+            option
+                .name("terms")
+                .description("Words to look for in the quote or author.")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("author")
+                .description("Only show quotes by authors whose name contains this.")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("before")
+                .description("Only show quotes from before this date, as DD/MM/YYYY.")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+        .create_option(|option| {
+            option
+                .name("after")
+                .description("Only show quotes from after this date, as DD/MM/YYYY.")
+                .kind(CommandOptionType::String)
+                .required(false)
+        })
+}
+
+pub async fn run(ctx: &Context, command: &ApplicationCommandInteraction) -> Result<()> {
+    instrument_interaction!("search", command, {
+        let guild_id = command
+            .guild_id
+            .context("search should only be run in guilds")?;
+
+        // the options are already separate here, so the terms are searched for as they are rather
+        // than parsed like the text command's arguments, where they'd be mistaken for options
+        let mut query = SearchQuery {
+            terms: string_option(command, "terms")
+                .map(|terms| terms.split_whitespace().map(String::from).collect())
+                .unwrap_or_default(),
+            author: string_option(command, "author").map(String::from),
+            ..Default::default()
+        };
+        for (option, date) in [("before", &mut query.before), ("after", &mut query.after)] {
+            if let Some(value) = string_option(command, option) {
+                match search::parse_date(value) {
+                    Ok(value) => *date = Some(value),
+                    Err(reply_content) => {
+                        return reply_ephemeral(ctx, command, &reply_content).await
+                    }
+                }
+            }
+        }
+
+        let (quotes, total_count) = archive::get(ctx)
+            .await
+            .search(guild_id, query.clone(), search::PAGE_SIZE, 0)
+            .await?;
+
+        if total_count == 0 {
+            return reply_ephemeral(ctx, command, "No quotes found.").await;
+        }
+
+        command
+            .create_interaction_response(&ctx.http, |r| {
+                r.kind(InteractionResponseType::ChannelMessageWithSource)
+                    .interaction_response_data(|d| {
+                        d.set_embed(search::results_embed(&quotes, 0, total_count))
+                            .set_components(search::page_buttons(0, total_count))
+                    })
+            })
+            .await
+            .context("failed to send search results")?;
+
+        let results_msg = command
+            .get_interaction_response(&ctx.http)
+            .await
+            .context("failed to get search results message")?;
+
+        search::paginate(
+            ctx,
+            results_msg,
+            command.user.id,
+            guild_id,
+            query,
+            total_count,
+        )
+        .await
+    })
+}
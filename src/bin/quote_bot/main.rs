@@ -1,19 +1,30 @@
 #[macro_use]
 extern crate tracing;
 
-use std::{collections::HashSet, env};
+use std::{collections::HashSet, env, sync::Arc};
 
 use anyhow::{Context, Result};
 use serenity::{model::prelude::UserId, prelude::*};
 use tracing_subscriber::util::SubscriberInitExt;
 
+mod archive;
+mod avatars;
 mod commands;
+mod generator;
 mod handler;
+mod interactions;
 mod log;
+mod mentions;
+mod ratelimit;
+mod search;
+mod themes;
 
 // note: this value is mirrored in src/commands/help.rs
 pub const EMBED_COLOR: [u8; 3] = [0x58, 0x65, 0xF2];
 
+const DEFAULT_DATABASE_PATH: &str = "quote_bot.db";
+const DEFAULT_THEMES_DIR: &str = "themes";
+
 async fn client() -> Result<Client> {
     let token =
         env::var("DISCORD_TOKEN").context("failed to load `DISCORD_TOKEN` environment variable")?;
@@ -33,9 +44,38 @@ async fn client() -> Result<Client> {
         HashSet::new()
     };
 
+    // an empty path would open a temporary database that's thrown away when the bot stops
+    let database_path = env::var("DATABASE_PATH")
+        .ok()
+        .filter(|path| !path.is_empty())
+        .unwrap_or_else(|| String::from(DEFAULT_DATABASE_PATH));
+    let archive = archive::Archive::open(&database_path)
+        .with_context(|| format!("failed to open quote archive at `{database_path}`"))?;
+
+    // an empty path would find no themes, leaving only the built-in default
+    let themes_dir = env::var("THEMES_DIR")
+        .ok()
+        .filter(|dir| !dir.is_empty())
+        .unwrap_or_else(|| String::from(DEFAULT_THEMES_DIR));
+    let themes = themes::Themes::load(&themes_dir)
+        .with_context(|| format!("failed to load themes from `{themes_dir}`"))?;
+
+    if let Some(fonts_dir) = env::var("FONTS_DIR").ok().filter(|dir| !dir.is_empty()) {
+        let font_count = quote_bot::render::load_extra_fonts(&fonts_dir)
+            .with_context(|| format!("failed to load fonts from `{fonts_dir}`"))?;
+        info!(font_count, fonts_dir, "Loaded fallback fonts");
+    }
+
     let client = Client::builder(token, intents)
         .event_handler(handler::Handler)
         .framework(commands::framework(owners).await)
+        .type_map_insert::<archive::Archive>(archive)
+        .type_map_insert::<themes::Themes>(Arc::new(themes))
+        .type_map_insert::<generator::EmojiCache>(Arc::new(generator::EmojiCache::cache()))
+        .type_map_insert::<avatars::AvatarCache>(Arc::new(avatars::AvatarCache::new()))
+        .type_map_insert::<ratelimit::UnsplashBucket>(Arc::new(Mutex::new(
+            ratelimit::UnsplashBucket::bucket(),
+        )))
         .await
         .expect("Discord client should build successfully");
 
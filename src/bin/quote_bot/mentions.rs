@@ -0,0 +1,106 @@
+use serenity::{
+    model::prelude::{ChannelId, GuildId, User, UserId},
+    prelude::*,
+    utils,
+};
+
+use crate::generator::Author;
+
+/// Replaces the user, role and channel mentions in `text` with the names Discord shows for them,
+/// using members' nicknames in `guild_id` where they have one. Mentions that can't be resolved
+/// are left as they are.
+pub async fn resolve(ctx: &Context, text: &str, guild_id: Option<GuildId>) -> String {
+    let mut resolved = String::with_capacity(text.len());
+
+    let mut rest = text;
+    while let Some(start) = rest.find('<') {
+        resolved.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let Some(end) = rest.find('>') else {
+            break;
+        };
+
+        match resolve_mention(ctx, &rest[..=end], guild_id).await {
+            Some(name) => {
+                resolved.push_str(&name);
+                rest = &rest[end + 1..];
+            }
+            None => {
+                resolved.push('<');
+                rest = &rest[1..];
+            }
+        }
+    }
+    resolved.push_str(rest);
+
+    resolved
+}
+
+/// Resolves an author given as a user mention to that user, attributed by their display name
+/// without the `@`. Authors with mentions alongside other text have them resolved as in
+/// [`resolve`].
+pub async fn resolve_author(ctx: &Context, author: &str, guild_id: Option<GuildId>) -> Author {
+    let author = author.trim();
+
+    let user_id = Some(author)
+        .filter(|author| author.ends_with('>'))
+        .and_then(utils::parse_username);
+    if let Some(user_id) = user_id {
+        if let Some((name, user)) = display_name(ctx, UserId(user_id), guild_id).await {
+            return Author {
+                name,
+                user: Some(user),
+            };
+        }
+    }
+
+    Author::named(resolve(ctx, author, guild_id).await)
+}
+
+/// The name shown for a single mention, such as `<@123>` or `<#456>`, if it is one.
+async fn resolve_mention(
+    ctx: &Context,
+    mention: &str,
+    guild_id: Option<GuildId>,
+) -> Option<String> {
+    if let Some(user_id) = utils::parse_username(mention) {
+        let (name, _) = display_name(ctx, UserId(user_id), guild_id).await?;
+
+        return Some(format!("@{name}"));
+    }
+
+    if let Some(role_id) = utils::parse_role(mention) {
+        let role = ctx.cache.role(guild_id?, role_id)?;
+
+        return Some(format!("@{}", role.name));
+    }
+
+    if let Some(channel_id) = utils::parse_channel(mention) {
+        let name = ChannelId(channel_id).name(&ctx.cache).await?;
+
+        return Some(format!("#{name}"));
+    }
+
+    None
+}
+
+/// A user's nickname in `guild_id` if they have one there, and otherwise their username, along
+/// with the user themselves.
+///
+/// Members are looked up in the cache first, falling back to fetching them from Discord.
+async fn display_name(
+    ctx: &Context,
+    user_id: UserId,
+    guild_id: Option<GuildId>,
+) -> Option<(String, User)> {
+    if let Some(guild_id) = guild_id {
+        if let Ok(member) = guild_id.member(ctx, user_id).await {
+            return Some((member.display_name().into_owned(), member.user));
+        }
+    }
+
+    let user = user_id.to_user(ctx).await.ok()?;
+
+    Some((user.name.clone(), user))
+}
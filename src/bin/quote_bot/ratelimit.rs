@@ -0,0 +1,75 @@
+use std::{
+    collections::VecDeque,
+    sync::Arc,
+    time::{Duration, Instant},
+};
+
+use serenity::prelude::*;
+
+pub struct Bucket {
+    time_span: Duration,
+    limit: usize,
+    uses: VecDeque<Instant>,
+}
+
+impl Bucket {
+    pub fn new(time_span: Duration, limit: usize) -> Self {
+        Self {
+            time_span,
+            limit,
+            uses: VecDeque::with_capacity(limit),
+        }
+    }
+
+    /// Takes a use from the bucket, returning how long until one frees up if it's empty.
+    pub fn take(&mut self) -> Result<(), Duration> {
+        let now = Instant::now();
+
+        while let Some(oldest_use) = self.uses.front() {
+            if now.duration_since(*oldest_use) >= self.time_span {
+                self.uses.pop_front();
+            } else {
+                break;
+            }
+        }
+
+        if self.uses.len() >= self.limit {
+            let oldest_use = self
+                .uses
+                .front()
+                .expect("full bucket should contain at least one use");
+
+            return Err(self.time_span - now.duration_since(*oldest_use));
+        }
+
+        self.uses.push_back(now);
+
+        Ok(())
+    }
+}
+
+/// Global bucket shared by every command that fetches images from Unsplash.
+pub struct UnsplashBucket;
+
+impl UnsplashBucket {
+    pub fn bucket() -> Bucket {
+        Bucket::new(Duration::from_secs(3600), 50)
+    }
+}
+
+impl TypeMapKey for UnsplashBucket {
+    type Value = Arc<Mutex<Bucket>>;
+}
+
+pub async fn take_unsplash(ctx: &Context) -> Result<(), Duration> {
+    let bucket = ctx
+        .data
+        .read()
+        .await
+        .get::<UnsplashBucket>()
+        .cloned()
+        .expect("Unsplash bucket should be inserted into client data");
+
+    let mut bucket = bucket.lock().await;
+    bucket.take()
+}
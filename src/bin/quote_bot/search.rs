@@ -0,0 +1,175 @@
+use std::time::Duration;
+
+use anyhow::{Context as _, Result};
+use chrono::NaiveDate;
+use serenity::{
+    builder::{CreateComponents, CreateEmbed},
+    model::{
+        application::{component::ButtonStyle, interaction::InteractionResponseType},
+        prelude::{GuildId, Message, UserId},
+    },
+    prelude::*,
+    utils::Colour,
+};
+
+use crate::{
+    archive::{self, QuoteRecord, SearchQuery},
+    generator, EMBED_COLOR,
+};
+
+pub const PAGE_SIZE: u32 = 5;
+
+const PAGINATION_TIMEOUT: Duration = Duration::from_secs(120);
+const MAX_QUOTE_PREVIEW_LENGTH: usize = 200;
+
+const PREVIOUS_PAGE_ID: &str = "search_previous_page";
+const NEXT_PAGE_ID: &str = "search_next_page";
+
+/// Parses search terms interspersed with `--author`, `--before` and `--after` options.
+///
+/// Errors are messages suitable for showing to the user.
+pub fn parse_query(args: impl IntoIterator<Item = String>) -> Result<SearchQuery, String> {
+    let mut query = SearchQuery::default();
+
+    let mut args = args.into_iter();
+    while let Some(arg) = args.next() {
+        let Some(option) = arg.strip_prefix("--") else {
+            query.terms.extend(arg.split_whitespace().map(String::from));
+            continue;
+        };
+
+        let value = args
+            .next()
+            .ok_or_else(|| format!("`--{option}` needs a value."))?;
+
+        match option {
+            "author" => query.author = Some(value),
+            "before" => query.before = Some(parse_date(&value)?),
+            "after" => query.after = Some(parse_date(&value)?),
+            _ => return Err(format!("Unknown option `--{option}`.")),
+        }
+    }
+
+    Ok(query)
+}
+
+pub fn parse_date(s: &str) -> Result<NaiveDate, String> {
+    NaiveDate::parse_from_str(s, generator::DATE_FORMAT)
+        .map_err(|_| String::from("Dates must be in DD/MM/YYYY format."))
+}
+
+pub fn page_count(total_count: u32) -> u32 {
+    total_count.div_ceil(PAGE_SIZE).max(1)
+}
+
+pub fn results_embed(quotes: &[QuoteRecord], page: u32, total_count: u32) -> CreateEmbed {
+    let [r, g, b] = EMBED_COLOR;
+
+    let description = quotes
+        .iter()
+        .map(|record| {
+            let mut text: String = record.text.chars().take(MAX_QUOTE_PREVIEW_LENGTH).collect();
+            if text.len() < record.text.len() {
+                text.push('\u{2026}');
+            }
+
+            format!(
+                "**\u{201C}{}\u{201D}**\n\u{2014} {}, {} \u{00B7} [Jump to quote]({})",
+                text,
+                record.author,
+                record.date.format(generator::DATE_FORMAT),
+                record.message_id.link(record.channel_id, record.guild_id),
+            )
+        })
+        .collect::<Vec<String>>()
+        .join("\n\n");
+
+    let mut embed = CreateEmbed::default();
+    embed
+        .title("Search results")
+        .description(description)
+        .colour(Colour::from_rgb(r, g, b))
+        .footer(|f| {
+            f.text(format!(
+                "Page {}/{} \u{00B7} {} {}",
+                page + 1,
+                page_count(total_count),
+                total_count,
+                if total_count == 1 { "quote" } else { "quotes" },
+            ))
+        });
+
+    embed
+}
+
+pub fn page_buttons(page: u32, total_count: u32) -> CreateComponents {
+    let mut components = CreateComponents::default();
+    components.create_action_row(|row| {
+        row.create_button(|button| {
+            button
+                .custom_id(PREVIOUS_PAGE_ID)
+                .label("Previous")
+                .style(ButtonStyle::Secondary)
+                .disabled(page == 0)
+        })
+        .create_button(|button| {
+            button
+                .custom_id(NEXT_PAGE_ID)
+                .label("Next")
+                .style(ButtonStyle::Secondary)
+                .disabled(page + 1 >= page_count(total_count))
+        })
+    });
+
+    components
+}
+
+/// Flips `results_msg` between pages of results as its buttons are pressed by `user_id`, removing
+/// the buttons once they've gone unused for a while.
+pub async fn paginate(
+    ctx: &Context,
+    mut results_msg: Message,
+    user_id: UserId,
+    guild_id: GuildId,
+    query: SearchQuery,
+    mut total_count: u32,
+) -> Result<()> {
+    let archive = archive::get(ctx).await;
+
+    let mut page: u32 = 0;
+    while let Some(interaction) = results_msg
+        .await_component_interaction(ctx)
+        .author_id(user_id)
+        .timeout(PAGINATION_TIMEOUT)
+        .await
+    {
+        page = match interaction.data.custom_id.as_str() {
+            PREVIOUS_PAGE_ID => page.saturating_sub(1),
+            NEXT_PAGE_ID => (page + 1).min(page_count(total_count) - 1),
+            _ => continue,
+        };
+
+        let (quotes, current_total_count) = archive
+            .search(guild_id, query.clone(), PAGE_SIZE, page * PAGE_SIZE)
+            .await?;
+        total_count = current_total_count;
+
+        interaction
+            .create_interaction_response(ctx, |r| {
+                r.kind(InteractionResponseType::UpdateMessage)
+                    .interaction_response_data(|d| {
+                        d.set_embed(results_embed(&quotes, page, total_count))
+                            .set_components(page_buttons(page, total_count))
+                    })
+            })
+            .await
+            .context("failed to update search results")?;
+    }
+
+    results_msg
+        .edit(ctx, |m| m.set_components(CreateComponents::default()))
+        .await
+        .context("failed to remove search result buttons")?;
+
+    Ok(())
+}
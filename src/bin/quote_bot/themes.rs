@@ -0,0 +1,101 @@
+use std::{collections::BTreeMap, fs, io, path::Path, sync::Arc};
+
+use anyhow::{Context as _, Result};
+use quote_bot::render::Theme;
+use serenity::{model::prelude::GuildId, prelude::*};
+
+use crate::archive;
+
+/// The theme used when a guild hasn't picked one, if it's present in the themes directory.
+pub const DEFAULT_THEME_NAME: &str = "light";
+
+/// Themes loaded from the themes directory, keyed by file name without the `.toml` extension.
+#[derive(Debug, Default)]
+pub struct Themes {
+    themes: BTreeMap<String, Theme>,
+}
+
+impl Themes {
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self> {
+        let dir = dir.as_ref();
+
+        let entries = match fs::read_dir(dir) {
+            Ok(entries) => entries,
+            Err(err) if err.kind() == io::ErrorKind::NotFound => {
+                warn!(dir = %dir.display(), "Themes directory not found, using built-in theme");
+                return Ok(Self::default());
+            }
+            Err(err) => return Err(err).context("failed to read themes directory"),
+        };
+
+        let mut themes = BTreeMap::new();
+        for entry in entries {
+            let path = entry.context("failed to read themes directory")?.path();
+            if path.extension().and_then(|extension| extension.to_str()) != Some("toml") {
+                continue;
+            }
+
+            let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) else {
+                continue;
+            };
+
+            let contents = fs::read_to_string(&path)
+                .with_context(|| format!("failed to read theme `{}`", path.display()))?;
+            let theme = Theme::from_toml(&contents)
+                .with_context(|| format!("failed to load theme `{}`", path.display()))?;
+
+            themes.insert(name.to_lowercase(), theme);
+        }
+
+        info!(count = themes.len(), "Loaded themes");
+
+        Ok(Self { themes })
+    }
+
+    pub fn get(&self, name: &str) -> Option<&Theme> {
+        self.themes.get(&name.to_lowercase())
+    }
+
+    pub fn names(&self) -> impl Iterator<Item = &str> {
+        self.themes.keys().map(String::as_str)
+    }
+
+    pub fn default_theme(&self) -> Theme {
+        self.get(DEFAULT_THEME_NAME).cloned().unwrap_or_default()
+    }
+}
+
+impl TypeMapKey for Themes {
+    type Value = Arc<Themes>;
+}
+
+pub async fn get(ctx: &Context) -> Arc<Themes> {
+    ctx.data
+        .read()
+        .await
+        .get::<Themes>()
+        .cloned()
+        .expect("themes should be inserted into client data")
+}
+
+/// Resolves the theme quotes should be rendered with in `guild_id`, falling back to the default
+/// theme outside guilds or if the guild's choice is unavailable.
+pub async fn guild_theme(ctx: &Context, guild_id: Option<GuildId>) -> Theme {
+    let themes = get(ctx).await;
+
+    let Some(guild_id) = guild_id else {
+        return themes.default_theme();
+    };
+
+    let theme_name = match archive::get(ctx).await.guild_theme(guild_id).await {
+        Ok(theme_name) => theme_name,
+        Err(err) => {
+            error!("Failed to load guild theme: {err:?}");
+            None
+        }
+    };
+
+    theme_name
+        .and_then(|theme_name| themes.get(&theme_name).cloned())
+        .unwrap_or_else(|| themes.default_theme())
+}
@@ -0,0 +1,209 @@
+use std::{collections::HashMap, path::PathBuf, sync::Arc};
+
+use anyhow::Result;
+use image::RgbaImage;
+
+use crate::image_cache::{ImageCache, ImageLocation};
+
+/// Images of custom emoji, keyed by emoji ID.
+pub type CustomEmojiImages = HashMap<u64, Arc<RgbaImage>>;
+
+/// A custom Discord emoji, written in message text as `<:name:id>`, or `<a:name:id>` if animated.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct CustomEmoji<'a> {
+    pub name: &'a str,
+    pub id: u64,
+    pub animated: bool,
+    /// The `:name:` form of the emoji, as shown when its image is unavailable.
+    pub shortcode: &'a str,
+    /// How many bytes of the text the markup covers.
+    pub len: usize,
+}
+
+impl<'a> CustomEmoji<'a> {
+    /// Parses custom emoji markup at the start of `text`.
+    pub fn match_start(text: &'a str) -> Option<Self> {
+        let (animated, rest) = if let Some(rest) = text.strip_prefix("<a:") {
+            (true, rest)
+        } else {
+            (false, text.strip_prefix("<:")?)
+        };
+
+        let (name, rest) = rest.split_once(':')?;
+        let (id, _) = rest.split_once('>')?;
+
+        let name_valid =
+            !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+        let id_valid = !id.is_empty() && id.chars().all(|c| c.is_ascii_digit());
+        if !name_valid || !id_valid {
+            return None;
+        }
+
+        let shortcode_start = if animated { 2 } else { 1 };
+        let shortcode_end = shortcode_start + name.len() + 2;
+
+        Some(Self {
+            name,
+            id: id.parse().ok()?,
+            animated,
+            shortcode: &text[shortcode_start..shortcode_end],
+            len: shortcode_end + id.len() + 1,
+        })
+    }
+
+    /// Finds every custom emoji in `text`.
+    pub fn find_all(text: &'a str) -> impl Iterator<Item = CustomEmoji<'a>> {
+        text.match_indices('<')
+            .filter_map(|(index, _)| CustomEmoji::match_start(&text[index..]))
+    }
+}
+
+/// Where custom emoji images come from.
+#[derive(Debug, Clone)]
+pub enum CustomEmojiSource {
+    /// Discord's CDN.
+    Discord,
+    /// PNG files in a local directory, named after each emoji's ID.
+    Directory(PathBuf),
+}
+
+/// Fetches custom emoji images, keeping hold of each one so it's only fetched once.
+pub struct CustomEmojiCache {
+    source: CustomEmojiSource,
+    images: ImageCache<u64>,
+}
+
+impl CustomEmojiCache {
+    const MAX_CACHED_IMAGES: usize = 1024;
+    const IMAGE_SIZE: u32 = 96;
+
+    pub fn new(source: CustomEmojiSource) -> Self {
+        Self {
+            source,
+            images: ImageCache::new(Self::MAX_CACHED_IMAGES),
+        }
+    }
+
+    /// Gets images for every custom emoji in `text`, leaving out any that couldn't be fetched.
+    pub async fn resolve(&self, text: &str) -> CustomEmojiImages {
+        let mut images = CustomEmojiImages::new();
+
+        for emoji in CustomEmoji::find_all(text) {
+            if images.contains_key(&emoji.id) {
+                continue;
+            }
+
+            match self.get(emoji.id).await {
+                Ok(image) => {
+                    images.insert(emoji.id, image);
+                }
+                Err(err) => {
+                    tracing::warn!(emoji_id = emoji.id, "Failed to fetch custom emoji: {err:?}");
+                }
+            }
+        }
+
+        images
+    }
+
+    pub async fn get(&self, id: u64) -> Result<Arc<RgbaImage>> {
+        let location = match &self.source {
+            CustomEmojiSource::Discord => ImageLocation::Url(format!(
+                "https://cdn.discordapp.com/emojis/{id}.png?size={}",
+                Self::IMAGE_SIZE
+            )),
+            CustomEmojiSource::Directory(dir) => ImageLocation::File(dir.join(format!("{id}.png"))),
+        };
+
+        self.images.get(id, location).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::NaiveDate;
+    use image::{Rgb, RgbImage, Rgba};
+
+    use super::*;
+    use crate::render::{self, RenderOptions};
+
+    const RED: Rgba<u8> = Rgba([255, 0, 0, 255]);
+    const BLUE: Rgba<u8> = Rgba([0, 0, 255, 255]);
+
+    /// Resolves and renders `quote`, with emoji images read from a directory holding a red emoji
+    /// with ID 1 and a blue one with ID 2.
+    fn render_with_emoji_directory(quote: &str) -> (CustomEmojiImages, RgbaImage) {
+        let dir = tempfile::tempdir().unwrap();
+        for (id, color) in [(1, RED), (2, BLUE)] {
+            RgbaImage::from_pixel(32, 32, color)
+                .save(dir.path().join(format!("{id}.png")))
+                .unwrap();
+        }
+
+        let cache = CustomEmojiCache::new(CustomEmojiSource::Directory(dir.path().into()));
+        let images = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()
+            .unwrap()
+            .block_on(cache.resolve(quote));
+
+        let card = render::render(
+            &RgbImage::from_pixel(300, 200, Rgb([128, 128, 128])),
+            quote,
+            "Author",
+            NaiveDate::from_ymd_opt(2024, 1, 1).unwrap(),
+            &images,
+            None,
+            &RenderOptions::default(),
+        );
+
+        (images, card.image)
+    }
+
+    fn contains_color(image: &RgbaImage, color: Rgba<u8>) -> bool {
+        image.pixels().any(|pixel| *pixel == color)
+    }
+
+    /// The left, top, right and bottom edges of everything drawn over the plain background.
+    fn content_bounds(image: &RgbaImage) -> [u32; 4] {
+        let background = *image.get_pixel(0, 0);
+
+        image
+            .enumerate_pixels()
+            .filter(|(_, _, pixel)| **pixel != background)
+            .fold(
+                [u32::MAX, u32::MAX, 0, 0],
+                |[left, top, right, bottom], (x, y, _)| {
+                    [left.min(x), top.min(y), right.max(x), bottom.max(y)]
+                },
+            )
+    }
+
+    #[test]
+    fn emoji_are_drawn_from_the_directory() {
+        let (images, image) = render_with_emoji_directory("hello <:wave:1> there <a:dance:2>");
+
+        assert_eq!(images.len(), 2);
+        assert!(contains_color(&image, RED));
+        assert!(contains_color(&image, BLUE));
+    }
+
+    #[test]
+    fn missing_emoji_fall_back_to_their_name() {
+        let (images, image) = render_with_emoji_directory("hello <:gone:3>");
+        let (_, expected_image) = render_with_emoji_directory("hello :gone:");
+
+        assert!(images.is_empty());
+        assert!(!contains_color(&image, RED) && !contains_color(&image, BLUE));
+
+        // the name's shaped on its own rather than along with the text around it, so it can land
+        // a fraction of a pixel away from where it would otherwise
+        let (bounds, expected_bounds) = (content_bounds(&image), content_bounds(&expected_image));
+        for (edge, expected_edge) in bounds.into_iter().zip(expected_bounds) {
+            assert!(
+                edge.abs_diff(expected_edge) <= 1,
+                "drawn within {bounds:?} rather than {expected_bounds:?}"
+            );
+        }
+    }
+}
@@ -0,0 +1,88 @@
+use std::{
+    collections::HashMap,
+    hash::Hash,
+    io::Cursor,
+    path::PathBuf,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+use anyhow::{Context, Result};
+use image::{io::Reader as ImageReader, RgbaImage};
+use reqwest::Client;
+
+/// Where an image is fetched from.
+#[derive(Debug, Clone)]
+pub enum ImageLocation {
+    Url(String),
+    File(PathBuf),
+}
+
+/// Fetches images, keeping hold of each one under a key so it's only fetched once.
+///
+/// Once `max_cached_images` are held, they're all dropped to make room.
+pub struct ImageCache<K> {
+    reqwest_client: Client,
+    max_cached_images: usize,
+    images: Mutex<HashMap<K, Arc<RgbaImage>>>,
+}
+
+impl<K: Eq + Hash> ImageCache<K> {
+    pub fn new(max_cached_images: usize) -> Self {
+        let reqwest_client = Client::builder()
+            .build()
+            .expect("reqwest client should build successfully");
+
+        Self {
+            reqwest_client,
+            max_cached_images,
+            images: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Gets the image held under `key`, fetching it from `location` if there isn't one yet.
+    pub async fn get(&self, key: K, location: ImageLocation) -> Result<Arc<RgbaImage>> {
+        if let Some(image) = self.cached_images().get(&key) {
+            return Ok(Arc::clone(image));
+        }
+
+        let image_data = match location {
+            ImageLocation::Url(url) => self
+                .reqwest_client
+                .get(url)
+                .send()
+                .await
+                .context("error occurred while sending request")?
+                .error_for_status()
+                .context("image download request failed")?
+                .bytes()
+                .await
+                .context("invalid image download response format")?
+                .to_vec(),
+            ImageLocation::File(path) => tokio::fs::read(path)
+                .await
+                .context("failed to read image file")?,
+        };
+
+        let image = ImageReader::new(Cursor::new(image_data))
+            .with_guessed_format()
+            .context("failed to guess image format")?
+            .decode()
+            .context("failed to decode image")?
+            .into_rgba8();
+        let image = Arc::new(image);
+
+        let mut cached_images = self.cached_images();
+        if cached_images.len() >= self.max_cached_images {
+            cached_images.clear();
+        }
+        cached_images.insert(key, Arc::clone(&image));
+
+        Ok(image)
+    }
+
+    fn cached_images(&self) -> MutexGuard<'_, HashMap<K, Arc<RgbaImage>>> {
+        self.images
+            .lock()
+            .expect("image cache mutex should not be poisoned")
+    }
+}
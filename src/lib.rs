@@ -1,3 +1,5 @@
 pub(crate) mod assets;
+pub mod custom_emoji;
+pub mod image_cache;
 pub mod render;
 pub mod unsplash;
@@ -2,314 +2,763 @@ use std::cmp;
 
 use chrono::NaiveDate;
 use image::{buffer::ConvertBuffer, imageops, Pixel, Rgb, RgbImage, Rgba, RgbaImage};
-use imageproc::drawing;
-use rusttype::{Font, Scale};
+use rusttype::Scale;
+use unicode_segmentation::UnicodeSegmentation;
+
+use crate::{assets::fonts::FontFamily, custom_emoji::CustomEmojiImages};
+
+mod animation;
+mod bidi;
+pub mod color;
+mod filter;
+mod format;
+mod layout;
+mod line_break;
+mod markdown;
+mod palette;
+mod rich_text;
+mod text_box;
+mod theme;
+mod typeset;
+
+pub use animation::{
+    encode_animation, render_animated, AnimationFormat, AnimationOptions, RenderedAnimation,
+};
+use bidi::Direction;
+pub use filter::{apply_filters, Duotone, DuotoneColor, Filter, ParseFilterError};
+pub use format::Format;
+pub use layout::Layout;
+use rich_text::RichText;
+use text_box::{BoxContent, TextBox};
+use typeset::Typesetter;
+
+pub use crate::assets::fonts::load_extra_fonts;
+pub use palette::{accent_color, extract_palette, PaletteColor};
+pub use theme::{TextColor, Theme, ThemeError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum HorizontalAlignment {
+    Left,
+    Center,
+    Right,
+}
+
+impl HorizontalAlignment {
+    /// Swaps left and right for text running right to left.
+    fn for_direction(self, direction: Direction) -> Self {
+        match (self, direction) {
+            (HorizontalAlignment::Left, Direction::RightToLeft) => HorizontalAlignment::Right,
+            (HorizontalAlignment::Right, Direction::RightToLeft) => HorizontalAlignment::Left,
+            _ => self,
+        }
+    }
+}
 
-use crate::assets::fonts::Lato;
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum VerticalAlignment {
+    Top,
+    Center,
+    Bottom,
+}
 
+/// Where a box sits within the space it's given, and how its text is aligned.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct Alignment {
+    pub horizontal: HorizontalAlignment,
+    pub vertical: VerticalAlignment,
+}
+
+impl Alignment {
+    pub const CENTER: Self = Self {
+        horizontal: HorizontalAlignment::Center,
+        vertical: VerticalAlignment::Center,
+    };
+
+    fn position(&self, dimensions: (u32, u32), max_dimensions: (u32, u32)) -> (u32, u32) {
+        let x = match self.horizontal {
+            HorizontalAlignment::Left => 0,
+            HorizontalAlignment::Center => max_dimensions.0 / 2 - dimensions.0 / 2,
+            HorizontalAlignment::Right => max_dimensions.0 - dimensions.0,
+        };
+        let y = match self.vertical {
+            VerticalAlignment::Top => 0,
+            VerticalAlignment::Center => max_dimensions.1 / 2 - dimensions.1 / 2,
+            VerticalAlignment::Bottom => max_dimensions.1 - dimensions.1,
+        };
+
+        (x, y)
+    }
+}
+
+/// A rendered card.
+pub struct RenderedCard {
+    pub image: RgbaImage,
+    /// Whether the quote was too long to fit even at the theme's minimum font size, and so was
+    /// cut short.
+    pub truncated: bool,
+}
+
+/// Everything about a card's look that isn't its content.
+#[derive(Default, Debug, Clone)]
+pub struct RenderOptions {
+    pub format: Format,
+    pub layout: Layout,
+    pub theme: Theme,
+}
+
+/// The content drawn onto a card.
+pub(crate) struct Card<'a> {
+    pub quote: &'a str,
+    pub author: &'a str,
+    pub timestamp: NaiveDate,
+    pub custom_emoji: &'a CustomEmojiImages,
+    pub avatar: Option<&'a RgbaImage>,
+}
+
+/// Renders a quote card. The background is cropped to the format's shape if it isn't already, then
+/// run through the theme's filters.
+/// Custom emoji in `quote` are drawn from `custom_emoji`, or written as `:name:` where their image
+/// is missing. The author's `avatar`, if given, is drawn as a circle beside the attribution.
 pub fn render(
     background_image: &RgbImage,
     quote: &str,
     author: &str,
     timestamp: NaiveDate,
-) -> RgbaImage {
-    let mut image: RgbaImage = background_image.clone().convert();
-    let dimensions = image.dimensions();
+    custom_emoji: &CustomEmojiImages,
+    avatar: Option<&RgbaImage>,
+    options: &RenderOptions,
+) -> RenderedCard {
+    let mut background_image = fit_background(background_image, options.format.dimensions());
+    apply_filters(&mut background_image, &options.theme.filters);
+    let mut image: RgbaImage = background_image.convert();
 
-    let average_color = calculate_average_color(background_image);
+    let scheme = color_scheme(&background_image, &options.theme);
 
-    const TEXT_BOX_OPACITY: f64 = 0.6;
-    let text_box_color = Rgba([255, 255, 255, (255.0 * TEXT_BOX_OPACITY) as u8]);
+    let card = Card {
+        quote,
+        author,
+        timestamp,
+        custom_emoji,
+        avatar,
+    };
+    let arrangement = options
+        .layout
+        .arrange(image.dimensions(), &card, &options.theme, scheme);
 
-    const MARGIN_MULTIPLIER: f64 = 0.2;
-    let margin_size = (dimensions.1 as f64 * MARGIN_MULTIPLIER) as u32;
+    let backdrops = arrangement.prepare_background(&mut image);
+    arrangement.draw(&mut image, &backdrops, 1.0, 1.0);
 
-    const QUOTE_PADDING_MULTIPLIER: f64 = 0.05;
-    let quote_padding_size = (dimensions.1 as f64 * QUOTE_PADDING_MULTIPLIER) as u32;
+    RenderedCard {
+        image,
+        truncated: arrangement.truncated,
+    }
+}
 
-    const QUOTE_BOX_HEIGHT_MULTIPLIER: f64 = 3.0 / 4.0;
+/// Picks legible text and box colours to go with `background_image`.
+fn color_scheme(background_image: &RgbImage, theme: &Theme) -> color::Scheme {
+    let average_color = calculate_average_color(background_image);
 
-    let max_quote_box_dimensions = (
-        dimensions.0 - margin_size * 2,
-        ((dimensions.1 - margin_size * 2) as f64 * QUOTE_BOX_HEIGHT_MULTIPLIER) as u32,
-    );
-    let max_quote_box_position = (margin_size, margin_size);
+    const PALETTE_SIZE: usize = 8;
+    let palette = extract_palette(background_image, PALETTE_SIZE);
+    let accent_color = accent_color(&palette).unwrap_or(average_color);
 
-    let quote_box = render_quote_box(
-        quote,
-        &average_color,
-        &text_box_color,
-        max_quote_box_dimensions,
-        quote_padding_size,
-    );
-    imageops::overlay(
-        &mut image,
-        &quote_box,
-        max_quote_box_position.0 as i64,
-        max_quote_box_position.1 as i64,
-    );
+    let text_color = match theme.text_color {
+        TextColor::Average => average_color,
+        TextColor::Accent => accent_color,
+        TextColor::Fixed(color) => color,
+    };
 
-    const BOX_GAP_MULTIPLIER: f64 = 0.025;
-    let box_gap_size = (dimensions.1 as f64 * BOX_GAP_MULTIPLIER) as u32;
+    // an outline keeps the text apart from whatever's behind it, so it only needs to stand out
+    // from the outline
+    if theme.text_outline_width > 0.0 {
+        return color::Scheme {
+            text_color: color::ensure_contrast(
+                text_color,
+                theme.text_outline_color,
+                color::MIN_CONTRAST_RATIO,
+            ),
+            box_color: theme.box_rgba(accent_color),
+        };
+    }
 
-    const ATTRIBUTION_PADDING_MULTIPLIER: f64 = 0.025;
-    let attribution_padding_size = (dimensions.1 as f64 * ATTRIBUTION_PADDING_MULTIPLIER) as u32;
+    color::legible_scheme(text_color, theme.box_rgba(accent_color), average_color)
+}
 
-    const ATTRIBUTION_BOX_HEIGHT_MULTIPLIER: f64 = 1.0 / 7.0;
+/// Crops `image` to the shape of `dimensions` around its centre and scales it to fill them.
+fn fit_background(image: &RgbImage, dimensions: (u32, u32)) -> RgbImage {
+    if image.dimensions() == dimensions {
+        return image.clone();
+    }
 
-    let max_attribution_box_dimensions = (
-        max_quote_box_dimensions.0,
-        ((dimensions.1 - margin_size * 2) as f64 * ATTRIBUTION_BOX_HEIGHT_MULTIPLIER) as u32,
+    let (width, height) = image.dimensions();
+    let scale = f64::min(
+        width as f64 / dimensions.0 as f64,
+        height as f64 / dimensions.1 as f64,
     );
-    let max_attribution_box_position = (
-        max_quote_box_position.0,
-        (max_quote_box_position.1 + max_quote_box_dimensions.1) + box_gap_size,
+    let crop_dimensions = (
+        ((dimensions.0 as f64 * scale) as u32).clamp(1, width),
+        ((dimensions.1 as f64 * scale) as u32).clamp(1, height),
     );
 
-    let attribution_box = render_attribution_box(
-        author,
-        timestamp,
-        &average_color,
-        &text_box_color,
-        max_attribution_box_dimensions,
-        attribution_padding_size,
-    );
-    imageops::overlay(
-        &mut image,
-        &attribution_box,
-        max_attribution_box_position.0 as i64,
-        max_attribution_box_position.1 as i64,
+    let cropped = imageops::crop_imm(
+        image,
+        (width - crop_dimensions.0) / 2,
+        (height - crop_dimensions.1) / 2,
+        crop_dimensions.0,
+        crop_dimensions.1,
     );
 
-    image
+    imageops::resize(
+        &cropped.to_image(),
+        dimensions.0,
+        dimensions.1,
+        imageops::FilterType::Triangle,
+    )
 }
 
-fn render_quote_box(
-    quote: &str,
-    text_color: &impl Pixel<Subpixel = u8>,
-    text_box_color: &impl Pixel<Subpixel = u8>,
-    max_dimensions: (u32, u32),
-    padding_size: u32,
-) -> RgbaImage {
-    let max_quote_text_dimensions = (
-        max_dimensions.0 - padding_size * 2,
-        max_dimensions.1 - padding_size * 2,
-    );
+/// The length of the shorter of a card's sides, which spacing and text sizes are measured against.
+fn shorter_side(dimensions: (u32, u32)) -> u32 {
+    dimensions.0.min(dimensions.1)
+}
 
-    let quote_text = render_quote_text(quote, text_color, max_quote_text_dimensions);
-    let quote_text_dimensions = quote_text.dimensions();
+/// How a box and the text inside it are drawn.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) struct BoxStyle {
+    pub text_color: Rgba<u8>,
+    pub box_color: Rgba<u8>,
+    pub corner_radius: u32,
+    pub padding_size: u32,
+    pub alignment: Alignment,
+    /// Whether wrapped lines are evened out rather than filled one at a time.
+    pub balance_lines: bool,
+    /// The smallest the text can be drawn before it's cut short instead, in pixels.
+    pub min_font_size: u32,
+    /// How much the card behind the box is blurred, for a frosted-glass look. Nothing's blurred if
+    /// this is 0.
+    pub backdrop_blur: u32,
+    pub border_width: u32,
+    pub border_color: Rgba<u8>,
+    /// The colour of the box's shadow, which isn't drawn if it's fully transparent.
+    pub shadow_color: Rgba<u8>,
+    pub shadow_blur: u32,
+    /// How far below the box its shadow falls.
+    pub shadow_offset: u32,
+    /// How thick the outline around the text is. No outline's drawn if this is 0.
+    pub text_outline_width: u32,
+    pub text_outline_color: Rgba<u8>,
+    /// The colour of the text's shadow, which isn't drawn if it's fully transparent.
+    pub text_shadow_color: Rgba<u8>,
+    pub text_shadow_blur: u32,
+    /// How far below the text its shadow falls.
+    pub text_shadow_offset: u32,
+}
 
-    let quote_box_dimensions = (
-        quote_text_dimensions.0 + padding_size * 2,
-        quote_text_dimensions.1 + padding_size * 2,
-    );
-    // center-aligned within max quote box
-    let quote_box_position = (
-        max_dimensions.0 / 2 - quote_box_dimensions.0 / 2,
-        max_dimensions.1 / 2 - quote_box_dimensions.1 / 2,
-    );
+/// A card with its text fitted and its boxes laid out, ready to be drawn over a background. It can
+/// be drawn any number of times, such as once for each frame of an animation, without being laid
+/// out again.
+pub(crate) struct Arrangement<'a> {
+    tint: Option<Tint>,
+    quote: Option<QuoteBox<'a>>,
+    attribution: Option<AttributionBox>,
+    /// Whether the quote had to be cut short to fit.
+    pub truncated: bool,
+}
 
-    let quote_text_position = (padding_size, padding_size);
+impl<'a> Arrangement<'a> {
+    pub(crate) fn new(
+        tint: Option<Tint>,
+        quote: Option<QuoteBox<'a>>,
+        attribution: Option<AttributionBox>,
+    ) -> Self {
+        // with no room for the quote at all, all of it's been cut
+        let truncated = quote.as_ref().is_none_or(|quote| quote.text.truncated);
+
+        Self {
+            tint,
+            quote,
+            attribution,
+            truncated,
+        }
+    }
 
-    let mut quote_box = RgbaImage::from_pixel(
-        quote_box_dimensions.0,
-        quote_box_dimensions.1,
-        text_box_color.to_rgba(),
-    );
-    imageops::overlay(
-        &mut quote_box,
-        &quote_text,
-        quote_text_position.0 as i64,
-        quote_text_position.1 as i64,
-    );
+    /// Draws the tint the layout puts over the background, if it has one, onto `image`, returning
+    /// blurred copies of what's then behind each box. Neither changes from one frame to the next
+    /// unless the background does.
+    pub(crate) fn prepare_background(&self, image: &mut RgbaImage) -> Backdrops {
+        if let Some(tint) = &self.tint {
+            let tint_image =
+                RgbaImage::from_pixel(tint.dimensions.0, tint.dimensions.1, tint.color);
+            imageops::overlay(
+                image,
+                &tint_image,
+                tint.position.0 as i64,
+                tint.position.1 as i64,
+            );
+        }
 
-    let mut image = RgbaImage::new(max_dimensions.0, max_dimensions.1);
-    imageops::overlay(
-        &mut image,
-        &quote_box,
-        quote_box_position.0 as i64,
-        quote_box_position.1 as i64,
-    );
+        Backdrops {
+            quote: self
+                .quote
+                .as_ref()
+                .and_then(|quote| quote.text_box.backdrop(image)),
+            attribution: self
+                .attribution
+                .as_ref()
+                .and_then(|attribution| attribution.text_box.backdrop(image)),
+        }
+    }
 
-    image
+    /// Draws the boxes onto a background prepared by [`Arrangement::prepare_background`], with
+    /// `quote_progress` of the quote typed out and the attribution faded in to
+    /// `attribution_opacity`, each from 0 to 1.
+    pub(crate) fn draw(
+        &self,
+        image: &mut RgbaImage,
+        backdrops: &Backdrops,
+        quote_progress: f32,
+        attribution_opacity: f32,
+    ) {
+        if let Some(quote) = &self.quote {
+            quote.draw(image, backdrops.quote.as_ref(), quote_progress);
+        }
+        if let Some(attribution) = &self.attribution {
+            attribution.text_box.draw(
+                image,
+                &attribution.content,
+                backdrops.attribution.as_ref(),
+                attribution_opacity,
+            );
+        }
+    }
 }
 
-fn render_quote_text(
-    quote: &str,
-    color: &impl Pixel<Subpixel = u8>,
-    max_dimensions: (u32, u32),
-) -> RgbaImage {
-    let color = color.to_rgba();
-
-    let font = Lato::bold();
+/// A flat colour drawn over part of the background, for layouts that provide their own backdrop.
+pub(crate) struct Tint {
+    pub color: Rgba<u8>,
+    pub position: (u32, u32),
+    pub dimensions: (u32, u32),
+}
 
-    const MAX_LINE_COUNT: u32 = 5;
-    const LINE_HEIGHT_MULTIPLIER: f64 = 1.3;
+/// Blurred copies of what's behind each box of an [`Arrangement`], for boxes drawn over a
+/// frosted-glass backdrop.
+pub(crate) struct Backdrops {
+    quote: Option<RgbaImage>,
+    attribution: Option<RgbaImage>,
+}
 
-    let min_line_height =
-        (max_dimensions.1 as f64 / (MAX_LINE_COUNT as f64 * LINE_HEIGHT_MULTIPLIER)) as u32;
-    let min_line_gap =
-        ((min_line_height as f64 / LINE_HEIGHT_MULTIPLIER) * (LINE_HEIGHT_MULTIPLIER - 1.0)) as u32;
+/// The quote, fitted into its box.
+pub(crate) struct QuoteBox<'a> {
+    text: QuoteText<'a>,
+    text_box: TextBox,
+    /// The whole quote, as most frames show it, so it's only drawn once.
+    full_content: BoxContent,
+}
 
-    let min_scale = Scale::uniform((min_line_height - min_line_gap) as f32);
+impl<'a> QuoteBox<'a> {
+    /// Fits the quote into a box within the area of `max_dimensions` at `max_position`, unless
+    /// there's no room for any of it.
+    pub(crate) fn new(
+        card: &Card<'a>,
+        style: &BoxStyle,
+        max_position: (u32, u32),
+        max_dimensions: (u32, u32),
+    ) -> Option<Self> {
+        let inner_dimensions = inner_dimensions(max_dimensions, style.padding_size);
+        if inner_dimensions.0 == 0 || inner_dimensions.1 == 0 {
+            return None;
+        }
 
-    let quote = {
-        let mut quote = String::from(quote.trim());
+        let text = QuoteText::fit(
+            card,
+            &style.text_color,
+            inner_dimensions,
+            style.alignment.horizontal,
+            style.balance_lines,
+            style.min_font_size,
+        );
+        let text_box = TextBox::new(text.dimensions, style, max_position, max_dimensions);
+        let full_content = text_box.content(text.draw(1.0));
 
-        quote = quote.lines().collect::<Vec<&str>>().join("");
+        Some(Self {
+            text,
+            text_box,
+            full_content,
+        })
+    }
 
-        if quote.starts_with('\'') || quote.starts_with('"') || quote.starts_with('\u{201C}') {
-            quote.remove(0);
-        }
-        if quote.ends_with('\'') || quote.ends_with('"') || quote.ends_with('\u{201D}') {
-            quote.pop();
+    fn draw(&self, image: &mut RgbaImage, backdrop: Option<&RgbaImage>, progress: f32) {
+        if progress >= 1.0 {
+            self.text_box.draw(image, &self.full_content, backdrop, 1.0);
+        } else {
+            let content = self.text_box.content(self.text.draw(progress));
+            self.text_box.draw(image, &content, backdrop, 1.0);
         }
+    }
+}
 
-        quote.insert(0, '\u{201C}');
-        quote.push('\u{201D}');
+/// The quote's lines, wrapped at the largest size they fit at.
+struct QuoteText<'a> {
+    typesetter: Typesetter<'a>,
+    color: Rgba<u8>,
+    /// The alignment of the lines, with left and right swapped for right-to-left text.
+    alignment: HorizontalAlignment,
+    font_size: u32,
+    line_height: u32,
+    lines: Vec<RichText>,
+    line_widths: Vec<u32>,
+    dimensions: (u32, u32),
+    /// Whether the quote had to be cut short because it doesn't fit even at the minimum font size.
+    truncated: bool,
+}
 
-        quote = wrap_text(&quote, &font, min_scale, max_dimensions.0);
+impl<'a> QuoteText<'a> {
+    /// Wraps the quote at the largest size it fits at, cutting it short if it doesn't fit even at
+    /// `min_font_size`.
+    fn fit(
+        card: &Card<'a>,
+        color: &impl Pixel<Subpixel = u8>,
+        max_dimensions: (u32, u32),
+        alignment: HorizontalAlignment,
+        balance_lines: bool,
+        min_font_size: u32,
+    ) -> Self {
+        let color = color.to_rgba();
+
+        let quote = {
+            let mut quote = String::from(card.quote.trim());
+
+            quote = quote.lines().collect::<Vec<&str>>().join("");
+
+            if quote.starts_with('\'') || quote.starts_with('"') || quote.starts_with('\u{201C}') {
+                quote.remove(0);
+            }
+            if quote.ends_with('\'') || quote.ends_with('"') || quote.ends_with('\u{201D}') {
+                quote.pop();
+            }
 
-        let mut quote_lines: Vec<&str> = quote.lines().collect();
-        if quote_lines.len() > MAX_LINE_COUNT as usize {
-            quote_lines = quote_lines.into_iter().take(5).collect();
-            quote = quote_lines.join("\n");
-            quote.pop();
-            quote.push('\u{2026}');
-        }
+            quote.insert(0, '\u{201C}');
+            quote.push('\u{201D}');
 
-        quote
-    };
+            markdown::parse(&quote)
+        };
 
-    let line_count: u32 = quote.lines().count() as u32;
+        let typesetter = Typesetter {
+            fonts: FontFamily::quote(),
+            custom_emoji: card.custom_emoji,
+            direction: Direction::of(quote.as_str()),
+        };
+        let alignment = alignment.for_direction(typesetter.direction);
 
-    if line_count == 0 {
-        panic!("wrapped quote should be made up of at least one line");
-    }
+        const LINE_HEIGHT_MULTIPLIER: f64 = 1.3;
 
-    let (height, scale) = if line_count == 1 {
-        let height = {
-            let height_max_dimensions =
-                drawing::text_size(Scale::uniform(max_dimensions.1 as f32), &font, &quote);
+        let line_height = |font_size: u32| (font_size as f64 * LINE_HEIGHT_MULTIPLIER) as u32;
+        let line_size = |font_size: u32, line: &RichText| {
+            let (width, height) = typesetter.text_size(Scale::uniform(font_size as f32), line);
 
-            let width_max_scale_factor = max_dimensions.0 as f64 / height_max_dimensions.0 as f64;
-            let width_max_height = (height_max_dimensions.1 as f64 * width_max_scale_factor) as u32;
+            (width as u32, height as u32)
+        };
+        let line_width = |font_size: u32, line: &RichText| line_size(font_size, line).0;
+        // fallback fonts can reach further below the baseline than the font size allows for, so the
+        // last line is given as much room as it actually takes up
+        let text_height = |font_size: u32, lines: &[RichText]| {
+            let last_line_height = lines.last().map_or(0, |line| line_size(font_size, line).1);
 
-            cmp::min_by_key(max_dimensions.1, width_max_height, |scale| {
-                let dimensions = drawing::text_size(Scale::uniform(*scale as f32), &font, &quote);
+            line_height(font_size) * (lines.len() as u32 - 1) + last_line_height.max(font_size)
+        };
 
-                dimensions.1 as u32
-            })
+        let wrap = |font_size: u32, split_words: bool| {
+            line_break::wrap_text(
+                &quote,
+                &typesetter,
+                Scale::uniform(font_size as f32),
+                max_dimensions.0,
+                balance_lines,
+                split_words,
+            )
+        };
+        let fits = |font_size: u32, lines: &[RichText]| {
+            text_height(font_size, lines) <= max_dimensions.1
+                && lines
+                    .iter()
+                    .all(|line| line_width(font_size, line) <= max_dimensions.0)
         };
-        let scale = Scale::uniform(height as f32);
 
-        (height, scale)
-    } else {
-        let height = (min_line_height * line_count) - min_line_gap;
+        let min_font_size = min_font_size.max(1);
 
-        (height, min_scale)
-    };
+        // binary search for the largest size the quote fits at without splitting any words
+        let mut fitting_lines = None;
+        let (mut low, mut high) = (min_font_size, max_dimensions.1.max(min_font_size));
+        while low <= high {
+            let font_size = low + (high - low) / 2;
 
-    let max_line_width = quote
-        .lines()
-        .map(|line| {
-            let dimensions = drawing::text_size(scale, &font, line);
+            let lines = wrap(font_size, false);
+            if fits(font_size, &lines) {
+                fitting_lines = Some((font_size, lines));
+                low = font_size + 1;
+            } else {
+                high = font_size - 1;
+            }
+        }
 
-            dimensions.0 as u32
-        })
-        .max()
-        .expect("wrapped quote should be made up of at least one line");
-    let dimensions = (max_line_width, height);
+        let (font_size, lines, truncated) = match fitting_lines {
+            Some((font_size, lines)) => (font_size, lines, false),
+            None => {
+                let font_size = min_font_size;
+                let mut lines = wrap(font_size, true);
+
+                let max_line_count = (max_dimensions.1.saturating_sub(font_size)
+                    / line_height(font_size)
+                    + 1) as usize;
+                let truncated = lines.len() > max_line_count;
+                if truncated {
+                    lines.truncate(max_line_count);
+
+                    let last_line = lines
+                        .last_mut()
+                        .expect("wrapped quote should be made up of at least one line");
+                    loop {
+                        let last_grapheme_len = last_line
+                            .as_str()
+                            .graphemes(true)
+                            .next_back()
+                            .map_or(0, str::len);
+                        last_line.truncate(last_line.len() - last_grapheme_len);
+                        last_line.truncate(last_line.as_str().trim_end().len());
+
+                        let mut shortened_line = last_line.clone();
+                        shortened_line.push_str("\u{2026}");
+                        if last_line.is_empty()
+                            || line_width(font_size, &shortened_line) <= max_dimensions.0
+                        {
+                            break;
+                        }
+                    }
+                    last_line.push_str("\u{2026}");
+                }
 
-    let mut image = RgbaImage::new(dimensions.0, dimensions.1);
-    for (line_index, line) in quote.lines().enumerate() {
-        let line_width = drawing::text_size(scale, &font, line).0 as u32;
-        let line_position = (
-            dimensions.0 / 2 - line_width / 2,
-            min_line_height * line_index as u32,
-        );
+                (font_size, lines, truncated)
+            }
+        };
 
-        drawing::draw_text_mut(
-            &mut image,
+        let max_line_width = lines
+            .iter()
+            .map(|line| line_width(font_size, line))
+            .max()
+            .expect("wrapped quote should be made up of at least one line");
+        // a quote cut short to fit at the minimum font size can still overhang the bottom slightly
+        let height = text_height(font_size, &lines).min(max_dimensions.1);
+        let dimensions = (max_line_width, height);
+
+        Self {
+            line_height: line_height(font_size),
+            line_widths: lines
+                .iter()
+                .map(|line| line_width(font_size, line))
+                .collect(),
+            typesetter,
             color,
-            line_position.0 as i32,
-            line_position.1 as i32,
-            scale,
-            &font,
-            line,
-        );
+            alignment,
+            font_size,
+            lines,
+            dimensions,
+            truncated,
+        }
     }
 
-    image
+    /// Draws the first `progress` of the quote, from 0 to 1. The rest is left out, but everything
+    /// is where it would be with all of it drawn, so nothing moves as it's typed out.
+    fn draw(&self, progress: f32) -> RgbaImage {
+        let scale = Scale::uniform(self.font_size as f32);
+
+        let grapheme_count: usize = self
+            .lines
+            .iter()
+            .map(|line| line.as_str().graphemes(true).count())
+            .sum();
+        let mut graphemes_left =
+            (grapheme_count as f32 * progress.clamp(0.0, 1.0)).round() as usize;
+
+        let mut image = RgbaImage::new(self.dimensions.0, self.dimensions.1);
+        for (line_index, (line, &line_width)) in
+            self.lines.iter().zip(&self.line_widths).enumerate()
+        {
+            if graphemes_left == 0 {
+                break;
+            }
+
+            let drawn_len = line
+                .as_str()
+                .grapheme_indices(true)
+                .nth(graphemes_left)
+                .map_or(line.len(), |(index, _)| index);
+            graphemes_left = graphemes_left.saturating_sub(line.as_str().graphemes(true).count());
+
+            let drawn_line = line.slice(0..drawn_len);
+
+            let line_x = match self.alignment {
+                HorizontalAlignment::Left => 0,
+                HorizontalAlignment::Center => self.dimensions.0 / 2 - line_width / 2,
+                HorizontalAlignment::Right => self.dimensions.0 - line_width,
+            };
+            // right-to-left lines are typed out from their right-hand end
+            let drawn_line_x = match self.typesetter.direction {
+                Direction::LeftToRight => line_x,
+                Direction::RightToLeft if drawn_len < line.len() => {
+                    let drawn_line_width = self.typesetter.text_size(scale, &drawn_line).0 as u32;
+
+                    line_x + line_width.saturating_sub(drawn_line_width)
+                }
+                Direction::RightToLeft => line_x,
+            };
+            let line_position = (drawn_line_x, self.line_height * line_index as u32);
+
+            self.typesetter.draw_text_mut(
+                &mut image,
+                self.color,
+                line_position.0 as i32,
+                line_position.1 as i32,
+                scale,
+                &drawn_line,
+            );
+        }
+
+        image
+    }
 }
 
-fn render_attribution_box(
-    author: &str,
-    timestamp: NaiveDate,
-    text_color: &impl Pixel<Subpixel = u8>,
-    text_box_color: &impl Pixel<Subpixel = u8>,
-    max_dimensions: (u32, u32),
-    padding_size: u32,
-) -> RgbaImage {
-    let max_attribution_text_dimensions = (
-        max_dimensions.0 - padding_size * 2,
-        max_dimensions.1 - padding_size * 2,
-    );
+/// The attribution, and the author's avatar if they have one, in its box.
+pub(crate) struct AttributionBox {
+    text_box: TextBox,
+    content: BoxContent,
+}
 
-    let attribution_text = render_attribution_text(
-        author,
-        timestamp,
-        text_color,
-        max_attribution_text_dimensions,
-    );
-    let attribution_text_dimensions = attribution_text.dimensions();
+impl AttributionBox {
+    /// Lays out the attribution in a box within the area of `max_dimensions` at `max_position`,
+    /// unless there's no room for it.
+    pub(crate) fn new(
+        card: &Card,
+        date_format: &str,
+        style: &BoxStyle,
+        max_position: (u32, u32),
+        max_dimensions: (u32, u32),
+    ) -> Option<Self> {
+        const AVATAR_GAP_MULTIPLIER: f64 = 0.3;
+
+        let inner_dimensions = inner_dimensions(max_dimensions, style.padding_size);
+        if inner_dimensions.0 == 0 || inner_dimensions.1 == 0 {
+            return None;
+        }
 
-    let attribution_box_dimensions = (
-        attribution_text_dimensions.0 + padding_size * 2,
-        attribution_text_dimensions.1 + padding_size * 2,
-    );
-    // center-aligned within max attribution box
-    let attribution_box_position = (
-        max_dimensions.0 / 2 - attribution_box_dimensions.0 / 2,
-        max_dimensions.1 / 2 - attribution_box_dimensions.1 / 2,
-    );
+        let attribution_text = render_attribution_text(
+            card.author,
+            card.timestamp,
+            date_format,
+            &style.text_color,
+            inner_dimensions,
+        );
 
-    let attribution_text_position = (padding_size, padding_size);
+        let Some(avatar) = card.avatar else {
+            return Some(Self::around(
+                attribution_text,
+                style,
+                max_position,
+                max_dimensions,
+            ));
+        };
 
-    let mut attribution_box = RgbaImage::from_pixel(
-        attribution_box_dimensions.0,
-        attribution_box_dimensions.1,
-        text_box_color.to_rgba(),
-    );
-    imageops::overlay(
-        &mut attribution_box,
-        &attribution_text,
-        attribution_text_position.0 as i64,
-        attribution_text_position.1 as i64,
-    );
+        // the avatar matches the text's height, which only shrinks once there's less room for it,
+        // so leaving room for one as tall as the text is now always leaves enough
+        let avatar_space =
+            (attribution_text.height() as f64 * (1.0 + AVATAR_GAP_MULTIPLIER)) as u32;
+        let attribution_text = render_attribution_text(
+            card.author,
+            card.timestamp,
+            date_format,
+            &style.text_color,
+            (
+                inner_dimensions.0.saturating_sub(avatar_space),
+                inner_dimensions.1,
+            ),
+        );
+
+        let avatar_size = attribution_text.height();
+        let avatar_gap = (avatar_size as f64 * AVATAR_GAP_MULTIPLIER) as u32;
+
+        let mut attribution = RgbaImage::new(
+            avatar_size + avatar_gap + attribution_text.width(),
+            avatar_size,
+        );
+        imageops::overlay(&mut attribution, &circle_crop(avatar, avatar_size), 0, 0);
+        imageops::overlay(
+            &mut attribution,
+            &attribution_text,
+            (avatar_size + avatar_gap) as i64,
+            0,
+        );
+
+        Some(Self::around(
+            attribution,
+            style,
+            max_position,
+            max_dimensions,
+        ))
+    }
+
+    fn around(
+        content: RgbaImage,
+        style: &BoxStyle,
+        max_position: (u32, u32),
+        max_dimensions: (u32, u32),
+    ) -> Self {
+        let text_box = TextBox::new(content.dimensions(), style, max_position, max_dimensions);
+        let content = text_box.content(content);
+
+        Self { text_box, content }
+    }
+}
 
-    let mut image = RgbaImage::new(max_dimensions.0, max_dimensions.1);
-    imageops::overlay(
-        &mut image,
-        &attribution_box,
-        attribution_box_position.0 as i64,
-        attribution_box_position.1 as i64,
+fn inner_dimensions(dimensions: (u32, u32), padding_size: u32) -> (u32, u32) {
+    (
+        dimensions.0.saturating_sub(padding_size * 2),
+        dimensions.1.saturating_sub(padding_size * 2),
+    )
+}
+
+/// Crops the middle of `image` to a circle `diameter` pixels across, with anti-aliased edges.
+fn circle_crop(image: &RgbaImage, diameter: u32) -> RgbaImage {
+    let (width, height) = image.dimensions();
+    let side = width.min(height);
+    let square = imageops::crop_imm(image, (width - side) / 2, (height - side) / 2, side, side);
+
+    let mut circle = imageops::resize(
+        &square.to_image(),
+        diameter,
+        diameter,
+        imageops::FilterType::Lanczos3,
     );
 
-    image
+    let radius = diameter as f64 / 2.0;
+    for (x, y, pixel) in circle.enumerate_pixels_mut() {
+        let dx = x as f64 + 0.5 - radius;
+        let dy = y as f64 + 0.5 - radius;
+
+        // how much of the pixel the circle covers, approximated by how far its centre is inside
+        let coverage = (radius - (dx * dx + dy * dy).sqrt() + 0.5).clamp(0.0, 1.0);
+        pixel.0[3] = (pixel.0[3] as f64 * coverage).round() as u8;
+    }
+
+    circle
 }
 
 fn render_attribution_text(
     author: &str,
     timestamp: NaiveDate,
+    date_format: &str,
     color: &impl Pixel<Subpixel = u8>,
     max_dimensions: (u32, u32),
 ) -> RgbaImage {
     let color = color.to_rgba();
 
-    let font = Lato::semibold_italic();
-
-    const TIMESTAMP_FORMAT: &str = "%d/%m/%Y";
     let attribution = {
         let mut author = String::from(author.trim());
 
@@ -320,79 +769,39 @@ fn render_attribution_text(
             author = String::from(author.trim_start());
         }
 
-        format!("{}, {}", author, timestamp.format(TIMESTAMP_FORMAT))
+        RichText::plain(&format!("{}, {}", author, timestamp.format(date_format)))
+    };
+
+    let typesetter = Typesetter {
+        fonts: FontFamily::attribution(),
+        custom_emoji: &CustomEmojiImages::new(),
+        direction: Direction::of(attribution.as_str()),
     };
 
     let height = {
         let height_max_dimensions =
-            drawing::text_size(Scale::uniform(max_dimensions.1 as f32), &font, &attribution);
+            typesetter.text_size(Scale::uniform(max_dimensions.1 as f32), &attribution);
 
         let width_max_scale_factor = max_dimensions.0 as f64 / height_max_dimensions.0 as f64;
-        let width_max_height = (height_max_dimensions.1 as f64 * width_max_scale_factor) as u32;
+        // the scale, not the height of the drawn text, is what grows in step with the width
+        let width_max_height = (max_dimensions.1 as f64 * width_max_scale_factor) as u32;
 
         cmp::min_by_key(max_dimensions.1, width_max_height, |scale| {
-            let dimensions = drawing::text_size(Scale::uniform(*scale as f32), &font, &attribution);
+            let dimensions = typesetter.text_size(Scale::uniform(*scale as f32), &attribution);
 
             dimensions.1 as u32
         })
     };
     let scale = Scale::uniform(height as f32);
 
-    let dimensions = (
-        drawing::text_size(scale, &font, &attribution).0 as u32,
-        height,
-    );
+    let dimensions = (typesetter.text_size(scale, &attribution).0 as u32, height);
 
     let mut image = RgbaImage::new(dimensions.0, dimensions.1);
-    drawing::draw_text_mut(&mut image, color, 0, 0, scale, &font, &attribution);
+    typesetter.draw_text_mut(&mut image, color, 0, 0, scale, &attribution);
 
     image
 }
 
-fn wrap_text(text: &str, font: &Font, scale: Scale, max_width: u32) -> String {
-    let text = String::from(text);
-
-    if drawing::text_size(scale, font, &text).0 as u32 <= max_width {
-        text
-    } else {
-        let mut last_fitting_whitespace_index: Option<usize> = None;
-        let mut current_text = String::with_capacity(text.capacity());
-        for (index, character) in text.chars().enumerate() {
-            current_text.push(character);
-
-            if drawing::text_size(scale, font, &current_text).0 as u32 <= max_width {
-                if character.is_whitespace() {
-                    last_fitting_whitespace_index = Some(index);
-                }
-            } else {
-                if character.is_whitespace() || last_fitting_whitespace_index.is_none() {
-                    last_fitting_whitespace_index = Some(index);
-                }
-
-                break;
-            }
-        }
-
-        let last_fitting_whitespace_index = last_fitting_whitespace_index
-            .expect("last_fitting_whitespace_index should not be None after for loop");
-
-        let current_line: String = text
-            .chars()
-            .take(last_fitting_whitespace_index + 1)
-            .collect();
-        let remaining_text: String = text
-            .chars()
-            .skip(last_fitting_whitespace_index + 1)
-            .collect();
-
-        format!(
-            "{}\n{}",
-            current_line,
-            wrap_text(&remaining_text, font, scale, max_width)
-        )
-    }
-}
-
 fn calculate_average_color(image: &RgbImage) -> Rgb<u8> {
     let pixels = image.pixels();
 
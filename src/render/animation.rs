@@ -0,0 +1,590 @@
+use std::{fmt, str::FromStr};
+
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use image::{
+    buffer::ConvertBuffer, codecs::webp::WebPEncoder, imageops, ColorType, Delay, Frame, RgbImage,
+    RgbaImage,
+};
+use serde::{Deserialize, Serialize};
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{apply_filters, color_scheme, fit_background, Card, RenderOptions};
+use crate::custom_emoji::CustomEmojiImages;
+
+/// How long each frame is shown for, in milliseconds.
+const FRAME_DELAY: u32 = 100;
+/// How long the finished card is shown for before the animation loops, in milliseconds.
+const HOLD_DELAY: u32 = 4000;
+
+const GRAPHEMES_PER_FRAME: usize = 3;
+const MAX_TYPING_FRAMES: usize = 30;
+const FADE_FRAMES: usize = 5;
+/// How many frames the finished card keeps moving for when panning over the background.
+const HOLD_FRAMES: usize = 30;
+
+/// How far the background is zoomed in by the end of a pan.
+const KEN_BURNS_ZOOM: f64 = 1.1;
+
+/// How big animated cards are compared to still ones, to keep their files small.
+const SIZE_MULTIPLIER: f64 = 0.5;
+
+/// The file format an animated card is encoded in.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum AnimationFormat {
+    #[default]
+    Gif,
+    WebP,
+}
+
+impl AnimationFormat {
+    pub const ALL: [AnimationFormat; 2] = [AnimationFormat::Gif, AnimationFormat::WebP];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            AnimationFormat::Gif => "gif",
+            AnimationFormat::WebP => "webp",
+        }
+    }
+
+    /// The extension of files in this format, which is the same as its name.
+    pub fn extension(&self) -> &'static str {
+        self.name()
+    }
+}
+
+impl fmt::Display for AnimationFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseAnimationFormatError(String);
+
+impl fmt::Display for ParseAnimationFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown animation format '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseAnimationFormatError {}
+
+impl FromStr for AnimationFormat {
+    type Err = ParseAnimationFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        AnimationFormat::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseAnimationFormatError(String::from(s)))
+    }
+}
+
+/// How an animated card moves, beyond the quote being typed out and the attribution fading in.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AnimationOptions {
+    /// Whether the background slowly pans and zooms throughout.
+    pub ken_burns: bool,
+}
+
+/// The frames of an animated card.
+pub struct RenderedAnimation {
+    pub frames: Vec<Frame>,
+    /// Whether the quote was too long to fit even at the theme's minimum font size, and so was
+    /// cut short.
+    pub truncated: bool,
+}
+
+/// The state of the card in a single frame.
+struct Keyframe {
+    quote_progress: f32,
+    attribution_opacity: f32,
+    delay: u32,
+}
+
+/// Renders a quote card that types out the quote and then fades in the attribution, as
+/// [`render`](super::render) draws it but at a smaller size.
+#[allow(clippy::too_many_arguments)]
+pub fn render_animated(
+    background_image: &RgbImage,
+    quote: &str,
+    author: &str,
+    timestamp: NaiveDate,
+    custom_emoji: &CustomEmojiImages,
+    avatar: Option<&RgbaImage>,
+    options: &RenderOptions,
+    animation_options: &AnimationOptions,
+) -> RenderedAnimation {
+    let dimensions = scale_dimensions(options.format.dimensions(), SIZE_MULTIPLIER);
+
+    let mut still_background = fit_background(background_image, dimensions);
+    apply_filters(&mut still_background, &options.theme.filters);
+    // panning needs more of the background than fits on the card at once
+    let pan_background = animation_options.ken_burns.then(|| {
+        let mut pan_background = fit_background(
+            background_image,
+            scale_dimensions(dimensions, KEN_BURNS_ZOOM),
+        );
+        apply_filters(&mut pan_background, &options.theme.filters);
+
+        pan_background
+    });
+
+    let scheme = color_scheme(&still_background, &options.theme);
+
+    // the text's only fitted once, and everything else that doesn't change between frames is only
+    // drawn once, so each frame just moves the background and reveals more of the card
+    let card = Card {
+        quote,
+        author,
+        timestamp,
+        custom_emoji,
+        avatar,
+    };
+    let arrangement = options
+        .layout
+        .arrange(dimensions, &card, &options.theme, scheme);
+
+    let still_frame = pan_background.is_none().then(|| {
+        let mut image: RgbaImage = still_background.convert();
+        let backdrops = arrangement.prepare_background(&mut image);
+
+        (image, backdrops)
+    });
+
+    let keyframes = keyframes(quote, animation_options);
+    let last_index = keyframes.len().saturating_sub(1).max(1);
+
+    let frames = keyframes
+        .into_iter()
+        .enumerate()
+        .map(|(index, keyframe)| {
+            let image = match &still_frame {
+                Some((image, backdrops)) => {
+                    let mut image = image.clone();
+                    arrangement.draw(
+                        &mut image,
+                        backdrops,
+                        keyframe.quote_progress,
+                        keyframe.attribution_opacity,
+                    );
+
+                    image
+                }
+                None => {
+                    let pan_background = pan_background
+                        .as_ref()
+                        .expect("there should be a background to pan when it isn't still");
+                    let progress = index as f64 / last_index as f64;
+                    let mut image: RgbaImage =
+                        ken_burns_frame(pan_background, dimensions, progress).convert();
+
+                    let backdrops = arrangement.prepare_background(&mut image);
+                    arrangement.draw(
+                        &mut image,
+                        &backdrops,
+                        keyframe.quote_progress,
+                        keyframe.attribution_opacity,
+                    );
+
+                    image
+                }
+            };
+
+            Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(keyframe.delay, 1))
+        })
+        .collect();
+
+    RenderedAnimation {
+        frames,
+        truncated: arrangement.truncated,
+    }
+}
+
+fn keyframes(quote: &str, animation_options: &AnimationOptions) -> Vec<Keyframe> {
+    let typing_frames = quote
+        .graphemes(true)
+        .count()
+        .div_ceil(GRAPHEMES_PER_FRAME)
+        .clamp(1, MAX_TYPING_FRAMES);
+
+    let typing = (1..=typing_frames).map(|frame| Keyframe {
+        quote_progress: frame as f32 / typing_frames as f32,
+        attribution_opacity: 0.0,
+        delay: FRAME_DELAY,
+    });
+    let fading = (1..=FADE_FRAMES).map(|frame| Keyframe {
+        quote_progress: 1.0,
+        attribution_opacity: frame as f32 / FADE_FRAMES as f32,
+        delay: FRAME_DELAY,
+    });
+    let mut keyframes: Vec<Keyframe> = typing.chain(fading).collect();
+
+    if animation_options.ken_burns {
+        keyframes.extend((0..HOLD_FRAMES).map(|_| Keyframe {
+            quote_progress: 1.0,
+            attribution_opacity: 1.0,
+            delay: FRAME_DELAY,
+        }));
+    } else if let Some(last_keyframe) = keyframes.last_mut() {
+        // nothing moves once the card's finished, so its last frame can just be shown for longer
+        last_keyframe.delay = HOLD_DELAY;
+    }
+
+    keyframes
+}
+
+/// The part of `background` shown `progress` of the way through a pan, zooming in from all of it
+/// to a `dimensions`-sized window in its bottom right.
+fn ken_burns_frame(background: &RgbImage, dimensions: (u32, u32), progress: f64) -> RgbImage {
+    let (width, height) = background.dimensions();
+
+    let zoom = 1.0 + (KEN_BURNS_ZOOM - 1.0) * progress;
+    let window_dimensions = (
+        ((width as f64 / zoom) as u32).clamp(1, width),
+        ((height as f64 / zoom) as u32).clamp(1, height),
+    );
+    let window_position = (
+        ((width - window_dimensions.0) as f64 * progress) as u32,
+        ((height - window_dimensions.1) as f64 * progress) as u32,
+    );
+
+    let window = imageops::crop_imm(
+        background,
+        window_position.0,
+        window_position.1,
+        window_dimensions.0,
+        window_dimensions.1,
+    );
+
+    imageops::resize(
+        &window.to_image(),
+        dimensions.0,
+        dimensions.1,
+        imageops::FilterType::Triangle,
+    )
+}
+
+/// Encodes the frames of an animation, shrinking them until the file is no bigger than
+/// `max_bytes`.
+pub fn encode_animation(
+    frames: &[Frame],
+    format: AnimationFormat,
+    max_bytes: usize,
+) -> Result<Vec<u8>> {
+    const MAX_ATTEMPTS: usize = 5;
+    // aim a little under the limit, as file size doesn't shrink exactly in step with area
+    const SHRINK_MARGIN: f64 = 0.9;
+
+    let Some(first_frame) = frames.first() else {
+        bail!("animation has no frames");
+    };
+    let (width, height) = first_frame.buffer().dimensions();
+
+    let mut scale = 1.0;
+    for _ in 0..MAX_ATTEMPTS {
+        let dimensions = scale_dimensions((width, height), scale);
+        let scaled_frames: Vec<(RgbImage, u32)> = frames
+            .iter()
+            .map(|frame| {
+                let image = imageops::resize(
+                    frame.buffer(),
+                    dimensions.0,
+                    dimensions.1,
+                    imageops::FilterType::Triangle,
+                );
+                let (delay_numer, delay_denom) = frame.delay().numer_denom_ms();
+
+                (image.convert(), delay_numer / delay_denom.max(1))
+            })
+            .collect();
+
+        let bytes = match format {
+            AnimationFormat::Gif => encode_gif(&scaled_frames)?,
+            AnimationFormat::WebP => encode_webp(&scaled_frames)?,
+        };
+        if bytes.len() <= max_bytes {
+            return Ok(bytes);
+        }
+
+        // file size goes roughly with the number of pixels
+        scale *= (max_bytes as f64 / bytes.len() as f64).sqrt() * SHRINK_MARGIN;
+    }
+
+    bail!("animation is still too large after shrinking it {MAX_ATTEMPTS} times");
+}
+
+/// The part of each frame that differs from the one before it, along with its position. The first
+/// frame is always whole.
+fn changed_regions(frames: &[(RgbImage, u32)]) -> Vec<(RgbImage, (u32, u32), u32)> {
+    let mut regions = Vec::with_capacity(frames.len());
+
+    let mut previous_image: Option<&RgbImage> = None;
+    for (image, delay) in frames {
+        let (position, dimensions) = match previous_image {
+            Some(previous_image) => changed_bounds(previous_image, image),
+            None => ((0, 0), image.dimensions()),
+        };
+
+        let region = imageops::crop_imm(image, position.0, position.1, dimensions.0, dimensions.1);
+        regions.push((region.to_image(), position, *delay));
+
+        previous_image = Some(image);
+    }
+
+    regions
+}
+
+/// The position and size of the smallest rectangle holding every pixel that differs between two
+/// equally sized images, with the position rounded down to even numbers as WebP needs. Identical
+/// images still get a single pixel.
+fn changed_bounds(previous: &RgbImage, current: &RgbImage) -> ((u32, u32), (u32, u32)) {
+    let (mut min_x, mut min_y, mut max_x, mut max_y) = (u32::MAX, u32::MAX, 0, 0);
+    for (x, y, pixel) in current.enumerate_pixels() {
+        if previous.get_pixel(x, y) != pixel {
+            min_x = min_x.min(x);
+            min_y = min_y.min(y);
+            max_x = max_x.max(x);
+            max_y = max_y.max(y);
+        }
+    }
+
+    if min_x == u32::MAX {
+        return ((0, 0), (1, 1));
+    }
+
+    let position = (min_x - min_x % 2, min_y - min_y % 2);
+    (position, (max_x + 1 - position.0, max_y + 1 - position.1))
+}
+
+fn encode_gif(frames: &[(RgbImage, u32)]) -> Result<Vec<u8>> {
+    // higher is faster, at the cost of how well each frame's colours are picked
+    const QUANTIZATION_SPEED: i32 = 10;
+
+    let (width, height) = frames
+        .first()
+        .map(|(image, _)| image.dimensions())
+        .context("animation has no frames")?;
+
+    let mut bytes = Vec::new();
+    {
+        let mut encoder = gif::Encoder::new(
+            &mut bytes,
+            u16::try_from(width).context("animation is too wide for a GIF")?,
+            u16::try_from(height).context("animation is too tall for a GIF")?,
+            &[],
+        )
+        .context("failed to start GIF")?;
+        encoder
+            .set_repeat(gif::Repeat::Infinite)
+            .context("failed to make GIF loop")?;
+
+        for (image, position, delay) in changed_regions(frames) {
+            let mut frame = gif::Frame::from_rgb_speed(
+                image.width() as u16,
+                image.height() as u16,
+                image.as_raw(),
+                QUANTIZATION_SPEED,
+            );
+            frame.left = position.0 as u16;
+            frame.top = position.1 as u16;
+            frame.delay = (delay / 10) as u16;
+            frame.dispose = gif::DisposalMethod::Keep;
+
+            encoder
+                .write_frame(&frame)
+                .context("failed to encode GIF frame")?;
+        }
+    }
+
+    Ok(bytes)
+}
+
+/// Encodes an animated WebP by wrapping each frame, losslessly encoded as a still WebP, in the
+/// chunks that make up an animation.
+fn encode_webp(frames: &[(RgbImage, u32)]) -> Result<Vec<u8>> {
+    const ANIMATION_FLAG: u8 = 0x02;
+
+    let (width, height) = frames
+        .first()
+        .map(|(image, _)| image.dimensions())
+        .context("animation has no frames")?;
+
+    let mut vp8x = vec![ANIMATION_FLAG, 0, 0, 0];
+    vp8x.extend_from_slice(&u24(width - 1));
+    vp8x.extend_from_slice(&u24(height - 1));
+
+    // an opaque black background, looping forever
+    let anim = [0, 0, 0, 255, 0, 0];
+
+    let mut chunks = Vec::new();
+    push_chunk(&mut chunks, b"VP8X", &vp8x);
+    push_chunk(&mut chunks, b"ANIM", &anim);
+
+    for (image, position, delay) in changed_regions(frames) {
+        let mut still = Vec::new();
+        WebPEncoder::new_lossless(&mut still)
+            .encode(
+                image.as_raw(),
+                image.width(),
+                image.height(),
+                ColorType::Rgb8,
+            )
+            .context("failed to encode WebP frame")?;
+
+        let mut anmf = Vec::new();
+        anmf.extend_from_slice(&u24(position.0 / 2));
+        anmf.extend_from_slice(&u24(position.1 / 2));
+        anmf.extend_from_slice(&u24(image.width() - 1));
+        anmf.extend_from_slice(&u24(image.height() - 1));
+        anmf.extend_from_slice(&u24(delay));
+        // drawn over the previous frame without blending, and left in place afterwards
+        anmf.push(0x02);
+        // the still image's chunks, without its own RIFF header
+        anmf.extend_from_slice(
+            still
+                .get(12..)
+                .context("WebP frame is missing its header")?,
+        );
+
+        push_chunk(&mut chunks, b"ANMF", &anmf);
+    }
+
+    let mut bytes = Vec::with_capacity(chunks.len() + 12);
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    bytes.extend_from_slice(b"WEBP");
+    bytes.extend_from_slice(&chunks);
+
+    Ok(bytes)
+}
+
+fn push_chunk(bytes: &mut Vec<u8>, fourcc: &[u8; 4], data: &[u8]) {
+    bytes.extend_from_slice(fourcc);
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+
+    // chunks are padded to an even length
+    if data.len() % 2 == 1 {
+        bytes.push(0);
+    }
+}
+
+/// A little-endian 24-bit integer, as WebP's animation chunks use.
+fn u24(value: u32) -> [u8; 3] {
+    let [a, b, c, _] = value.to_le_bytes();
+    [a, b, c]
+}
+
+fn scale_dimensions(dimensions: (u32, u32), scale: f64) -> (u32, u32) {
+    (
+        ((dimensions.0 as f64 * scale) as u32).max(1),
+        ((dimensions.1 as f64 * scale) as u32).max(1),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use image::{codecs::gif::GifDecoder, codecs::webp::WebPDecoder, AnimationDecoder, Rgba};
+
+    use super::*;
+
+    const FRAME_COUNT: usize = 6;
+    const DIMENSIONS: (u32, u32) = (120, 80);
+
+    /// A square moving across a background of noise, which compresses poorly enough for the size
+    /// cap to matter.
+    fn frames() -> Vec<Frame> {
+        let mut seed: u32 = 1;
+        let background = RgbaImage::from_fn(DIMENSIONS.0, DIMENSIONS.1, |_, _| {
+            seed = seed.wrapping_mul(1_664_525).wrapping_add(1_013_904_223);
+            let [r, g, b, _] = seed.to_le_bytes();
+
+            Rgba([r, g, b, 255])
+        });
+
+        (0..FRAME_COUNT)
+            .map(|index| {
+                let mut image = background.clone();
+                let square = RgbaImage::from_pixel(20, 20, Rgba([255, 0, 0, 255]));
+                imageops::overlay(&mut image, &square, index as i64 * 15, 30);
+
+                Frame::from_parts(image, 0, 0, Delay::from_numer_denom_ms(FRAME_DELAY, 1))
+            })
+            .collect()
+    }
+
+    fn decode(bytes: &[u8], format: AnimationFormat) -> Vec<Frame> {
+        match format {
+            AnimationFormat::Gif => GifDecoder::new(bytes).unwrap().into_frames(),
+            AnimationFormat::WebP => WebPDecoder::new(bytes).unwrap().into_frames(),
+        }
+        .collect_frames()
+        .unwrap()
+    }
+
+    #[test]
+    fn every_frame_is_encoded() {
+        let frames = frames();
+
+        for format in AnimationFormat::ALL {
+            let bytes = encode_animation(&frames, format, usize::MAX).unwrap();
+            let decoded = decode(&bytes, format);
+
+            assert_eq!(
+                decoded.len(),
+                FRAME_COUNT,
+                "{format} has the wrong frame count"
+            );
+            for frame in &decoded {
+                assert_eq!(frame.buffer().dimensions(), DIMENSIONS);
+            }
+        }
+    }
+
+    #[test]
+    fn webp_frames_are_lossless() {
+        let frames = frames();
+
+        let bytes = encode_animation(&frames, AnimationFormat::WebP, usize::MAX).unwrap();
+        let decoded = decode(&bytes, AnimationFormat::WebP);
+
+        // only the part of each frame that changed is stored, so this also checks each part is put
+        // back where it came from
+        for (frame, decoded_frame) in frames.iter().zip(&decoded) {
+            assert_eq!(frame.buffer(), decoded_frame.buffer());
+        }
+    }
+
+    #[test]
+    fn animations_are_shrunk_to_fit() {
+        let frames = frames();
+
+        for format in AnimationFormat::ALL {
+            let full_size = encode_animation(&frames, format, usize::MAX).unwrap().len();
+            let max_bytes = full_size / 3;
+
+            let bytes = encode_animation(&frames, format, max_bytes).unwrap();
+            assert!(
+                bytes.len() <= max_bytes,
+                "{format} is {} bytes, over the cap of {max_bytes}",
+                bytes.len()
+            );
+
+            let decoded = decode(&bytes, format);
+            assert_eq!(decoded.len(), FRAME_COUNT);
+
+            let (width, height) = decoded[0].buffer().dimensions();
+            assert!(width < DIMENSIONS.0 && height < DIMENSIONS.1);
+        }
+    }
+
+    #[test]
+    fn animations_that_cant_shrink_enough_are_rejected() {
+        for format in AnimationFormat::ALL {
+            assert!(encode_animation(&frames(), format, 1).is_err());
+        }
+    }
+}
@@ -0,0 +1,44 @@
+use unicode_bidi::{Level, ParagraphBidiInfo};
+
+/// Which way a paragraph of text runs.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Direction {
+    #[default]
+    LeftToRight,
+    RightToLeft,
+}
+
+impl Direction {
+    /// The direction of a paragraph, going by its first strongly directional character as in the
+    /// Unicode bidirectional algorithm. Text without one runs left to right.
+    pub fn of(text: &str) -> Self {
+        match unicode_bidi::get_base_direction(text) {
+            unicode_bidi::Direction::Rtl => Direction::RightToLeft,
+            _ => Direction::LeftToRight,
+        }
+    }
+
+    fn level(self) -> Level {
+        match self {
+            Direction::LeftToRight => Level::ltr(),
+            Direction::RightToLeft => Level::rtl(),
+        }
+    }
+}
+
+/// The embedding level of each byte of a line of text from a paragraph running in `direction`.
+pub fn levels(line: &str, direction: Direction) -> Vec<Level> {
+    ParagraphBidiInfo::new(line, Some(direction.level())).reordered_levels(0..line.len())
+}
+
+/// Puts the runs of a line, each paired with its embedding level, into the order they're
+/// displayed in from left to right.
+pub fn reorder<T>(runs: Vec<(Level, T)>) -> Vec<T> {
+    let levels: Vec<Level> = runs.iter().map(|(level, _)| *level).collect();
+    let mut runs: Vec<Option<T>> = runs.into_iter().map(|(_, run)| Some(run)).collect();
+
+    ParagraphBidiInfo::reorder_visual(&levels)
+        .into_iter()
+        .map(|index| runs[index].take().expect("each run should be placed once"))
+        .collect()
+}
@@ -0,0 +1,197 @@
+use image::{Rgb, Rgba};
+
+/// The minimum contrast ratio WCAG 2 requires for normal-sized text.
+pub const MIN_CONTRAST_RATIO: f64 = 4.5;
+
+const BLACK: Rgb<u8> = Rgb([0, 0, 0]);
+const WHITE: Rgb<u8> = Rgb([255, 255, 255]);
+
+// how finely colours and opacities are stepped when searching for enough contrast
+const ADJUSTMENT_STEPS: u32 = 64;
+
+/// A text colour and the box colour it's drawn on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Scheme {
+    pub text_color: Rgb<u8>,
+    pub box_color: Rgba<u8>,
+}
+
+/// The relative luminance of a colour, as defined by WCAG 2.
+pub fn relative_luminance(color: Rgb<u8>) -> f64 {
+    let [r, g, b] = color.0.map(|channel| {
+        let channel = channel as f64 / 255.0;
+
+        if channel <= 0.04045 {
+            channel / 12.92
+        } else {
+            ((channel + 0.055) / 1.055).powf(2.4)
+        }
+    });
+
+    0.2126 * r + 0.7152 * g + 0.0722 * b
+}
+
+/// The WCAG 2 contrast ratio between two colours, from 1 (none) to 21 (black on white).
+pub fn contrast_ratio(a: Rgb<u8>, b: Rgb<u8>) -> f64 {
+    let (a, b) = (relative_luminance(a), relative_luminance(b));
+    let (lighter, darker) = if a > b { (a, b) } else { (b, a) };
+
+    (lighter + 0.05) / (darker + 0.05)
+}
+
+/// The colour seen when `top` is drawn over `bottom`.
+pub fn composite(top: Rgba<u8>, bottom: Rgb<u8>) -> Rgb<u8> {
+    let alpha = top.0[3] as f64 / 255.0;
+
+    mix(bottom, Rgb([top.0[0], top.0[1], top.0[2]]), alpha)
+}
+
+/// Moves `text_color` towards black or white, whichever contrasts more with `background`, until
+/// it reaches `min_ratio`. Gives up at black or white if neither is enough.
+pub fn ensure_contrast(text_color: Rgb<u8>, background: Rgb<u8>, min_ratio: f64) -> Rgb<u8> {
+    if contrast_ratio(text_color, background) >= min_ratio {
+        return text_color;
+    }
+
+    let target = most_contrasting_extreme(background);
+
+    (1..=ADJUSTMENT_STEPS)
+        .map(|step| mix(text_color, target, step as f64 / ADJUSTMENT_STEPS as f64))
+        .find(|color| contrast_ratio(*color, background) >= min_ratio)
+        .unwrap_or(target)
+}
+
+/// Adjusts a text and box colour so the text is legible once the box is drawn over
+/// `background`, keeping as close to the original colours as possible.
+///
+/// The text colour is adjusted first. If no text colour is legible on the box, this falls back to
+/// black text on a white box or white text on a black box, made just opaque enough.
+pub fn legible_scheme(text_color: Rgb<u8>, box_color: Rgba<u8>, background: Rgb<u8>) -> Scheme {
+    let box_background = composite(box_color, background);
+
+    let text_color = ensure_contrast(text_color, box_background, MIN_CONTRAST_RATIO);
+    if contrast_ratio(text_color, box_background) >= MIN_CONTRAST_RATIO {
+        return Scheme {
+            text_color,
+            box_color,
+        };
+    }
+
+    let text_color = most_contrasting_extreme(box_background);
+    let Rgb([r, g, b]) = if text_color == BLACK { WHITE } else { BLACK };
+
+    let box_color = (0..=ADJUSTMENT_STEPS)
+        .map(|step| {
+            let opacity =
+                box_color.0[3] as u32 + (255 - box_color.0[3] as u32) * step / ADJUSTMENT_STEPS;
+
+            Rgba([r, g, b, opacity as u8])
+        })
+        .find(|box_color| {
+            contrast_ratio(text_color, composite(*box_color, background)) >= MIN_CONTRAST_RATIO
+        })
+        .unwrap_or(Rgba([r, g, b, 255]));
+
+    Scheme {
+        text_color,
+        box_color,
+    }
+}
+
+fn most_contrasting_extreme(background: Rgb<u8>) -> Rgb<u8> {
+    if contrast_ratio(BLACK, background) >= contrast_ratio(WHITE, background) {
+        BLACK
+    } else {
+        WHITE
+    }
+}
+
+/// `color` with an opacity from 0 to 1.
+pub fn with_opacity(Rgb([r, g, b]): Rgb<u8>, opacity: f64) -> Rgba<u8> {
+    Rgba([r, g, b, (255.0 * opacity) as u8])
+}
+
+/// Linearly interpolates from `from` to `to`, where `amount` is between 0 and 1.
+pub fn mix(from: Rgb<u8>, to: Rgb<u8>, amount: f64) -> Rgb<u8> {
+    let mut mixed = from;
+    for (channel, to) in mixed.0.iter_mut().zip(to.0) {
+        *channel = (*channel as f64 + (to as f64 - *channel as f64) * amount).round() as u8;
+    }
+
+    mixed
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MID_GREY: Rgb<u8> = Rgb([128, 128, 128]);
+
+    #[test]
+    fn luminance_spans_black_to_white() {
+        assert_eq!(relative_luminance(BLACK), 0.0);
+        assert!((relative_luminance(WHITE) - 1.0).abs() < 1e-9);
+        assert!((relative_luminance(MID_GREY) - 0.2158).abs() < 1e-3);
+    }
+
+    #[test]
+    fn black_on_white_is_21_to_1() {
+        assert!((contrast_ratio(BLACK, WHITE) - 21.0).abs() < 1e-9);
+        assert_eq!(contrast_ratio(BLACK, WHITE), contrast_ratio(WHITE, BLACK));
+        assert_eq!(contrast_ratio(MID_GREY, MID_GREY), 1.0);
+    }
+
+    #[test]
+    fn legible_colors_are_left_alone() {
+        let text_color = Rgb([20, 30, 40]);
+
+        assert_eq!(
+            ensure_contrast(text_color, WHITE, MIN_CONTRAST_RATIO),
+            text_color
+        );
+    }
+
+    #[test]
+    fn close_colors_are_pushed_apart() {
+        for (text_color, background) in [
+            (Rgb([140, 140, 140]), MID_GREY),
+            (Rgb([200, 60, 60]), Rgb([190, 70, 70])),
+            (Rgb([240, 240, 200]), WHITE),
+            (Rgb([10, 10, 30]), BLACK),
+        ] {
+            let adjusted = ensure_contrast(text_color, background, MIN_CONTRAST_RATIO);
+
+            assert!(
+                contrast_ratio(adjusted, background) >= MIN_CONTRAST_RATIO,
+                "{text_color:?} on {background:?} was only adjusted to {adjusted:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn text_on_a_mid_grey_box_is_legible() {
+        for background in [BLACK, MID_GREY, WHITE] {
+            for box_opacity in [0, 128, 255] {
+                let scheme = legible_scheme(
+                    MID_GREY,
+                    with_opacity(MID_GREY, box_opacity as f64 / 255.0),
+                    background,
+                );
+
+                let box_background = composite(scheme.box_color, background);
+                assert!(
+                    contrast_ratio(scheme.text_color, box_background) >= MIN_CONTRAST_RATIO,
+                    "illegible on a box of opacity {box_opacity} over {background:?}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn an_opaque_box_hides_the_background() {
+        let box_color = Rgba([10, 20, 30, 255]);
+
+        assert_eq!(composite(box_color, WHITE), Rgb([10, 20, 30]));
+        assert_eq!(composite(Rgba([0, 0, 0, 0]), MID_GREY), MID_GREY);
+    }
+}
@@ -0,0 +1,422 @@
+use std::{fmt, str::FromStr};
+
+use image::{imageops, Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    color,
+    palette::{accent_color, extract_palette, PaletteColor},
+    shorter_side,
+    theme::hex_color,
+};
+
+/// An effect applied to the background photo before anything is drawn on it.
+///
+/// Filters are applied one after another, so each works on the result of the one before.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum Filter {
+    /// Gaussian blur, with a standard deviation given as a fraction of the shorter side.
+    Blur(f64),
+    /// Scales every channel, darkening the photo below 1 and brightening it above.
+    Brightness(f64),
+    /// Fades the colour out, from 0 for none to 1 for entirely grey.
+    Desaturate(f64),
+    /// Maps the photo's shadows to one colour and its highlights to another.
+    Duotone(Duotone),
+    /// Darkens towards the corners, from 0 for not at all to 1 for black corners.
+    Vignette(f64),
+    /// Adds film grain, from 0 for none to 1 for noise as strong as the photo itself.
+    Grain(f64),
+}
+
+impl Filter {
+    pub(crate) fn validate(&self) -> Result<(), &'static str> {
+        let in_range = match *self {
+            Filter::Blur(radius) => (0.0..=0.1).contains(&radius),
+            Filter::Brightness(factor) => (0.0..=2.0).contains(&factor),
+            Filter::Desaturate(amount) | Filter::Vignette(amount) | Filter::Grain(amount) => {
+                (0.0..=1.0).contains(&amount)
+            }
+            Filter::Duotone(_) => true,
+        };
+
+        if !in_range {
+            return Err(match self {
+                Filter::Blur(_) => "`blur` must be between 0 and 0.1",
+                Filter::Brightness(_) => "`brightness` must be between 0 and 2",
+                _ => "`desaturate`, `vignette` and `grain` must be between 0 and 1",
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Parses a comma-separated list of filters, such as `blur:0.01,brightness:0.7,grain`, where
+    /// filters without a value get a moderate default. `none` is an empty list, and empty entries,
+    /// such as after a trailing comma, are skipped.
+    pub fn parse_list(s: &str) -> Result<Vec<Filter>, ParseFilterError> {
+        let s = s.trim();
+        if s.eq_ignore_ascii_case("none") {
+            return Ok(Vec::new());
+        }
+
+        s.split(',')
+            .map(str::trim)
+            .filter(|filter| !filter.is_empty())
+            .map(str::parse)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFilterError(String);
+
+impl fmt::Display for ParseFilterError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.0)
+    }
+}
+
+impl std::error::Error for ParseFilterError {}
+
+impl FromStr for Filter {
+    type Err = ParseFilterError;
+
+    /// Parses a single filter, written as `name` or `name:value`, or for duotone as
+    /// `duotone:shadows:highlights`.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let name = parts.next().unwrap_or_default().to_lowercase();
+        let values: Vec<&str> = parts.collect();
+
+        let amount = |default: f64| match values.as_slice() {
+            [] => Ok(default),
+            [value] => value
+                .parse::<f64>()
+                .map_err(|_| ParseFilterError(format!("invalid `{name}` value `{value}`"))),
+            _ => Err(ParseFilterError(format!("`{name}` takes a single value"))),
+        };
+
+        let filter = match name.as_str() {
+            "blur" => Filter::Blur(amount(0.005)?),
+            "brightness" => Filter::Brightness(amount(0.7)?),
+            "desaturate" => Filter::Desaturate(amount(1.0)?),
+            "vignette" => Filter::Vignette(amount(0.5)?),
+            "grain" => Filter::Grain(amount(0.1)?),
+            "duotone" => Filter::Duotone(match values.as_slice() {
+                [] => Duotone::default(),
+                [shadows, highlights] => Duotone {
+                    shadows: shadows.parse()?,
+                    highlights: highlights.parse()?,
+                },
+                _ => {
+                    return Err(ParseFilterError(String::from(
+                        "`duotone` takes either no colours or a shadow and a highlight colour",
+                    )))
+                }
+            }),
+            _ => {
+                return Err(ParseFilterError(format!(
+                    "unknown filter `{name}`, expected `blur`, `brightness`, `desaturate`, `duotone`, `vignette` or `grain`"
+                )))
+            }
+        };
+
+        filter
+            .validate()
+            .map_err(|reason| ParseFilterError(String::from(reason)))?;
+
+        Ok(filter)
+    }
+}
+
+/// The two colours of a [`Filter::Duotone`].
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(default, deny_unknown_fields)]
+pub struct Duotone {
+    pub shadows: DuotoneColor,
+    pub highlights: DuotoneColor,
+}
+
+impl Default for Duotone {
+    fn default() -> Self {
+        Self {
+            shadows: DuotoneColor::Darkest,
+            highlights: DuotoneColor::Accent,
+        }
+    }
+}
+
+/// One of the colours of a duotone, either taken from the photo's palette or fixed.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum DuotoneColor {
+    /// The darkest colour in the photo's palette.
+    Darkest,
+    /// The lightest colour in the photo's palette.
+    Lightest,
+    /// The most prominent colourful entry in the photo's palette.
+    Accent,
+    /// A fixed colour.
+    Fixed(#[serde(with = "hex_color")] Rgb<u8>),
+}
+
+impl DuotoneColor {
+    fn resolve(&self, palette: &[PaletteColor]) -> Rgb<u8> {
+        let by_luminance = |a: &&PaletteColor, b: &&PaletteColor| {
+            color::relative_luminance(a.color).total_cmp(&color::relative_luminance(b.color))
+        };
+
+        let color = match self {
+            DuotoneColor::Darkest => palette.iter().min_by(by_luminance).map(|entry| entry.color),
+            DuotoneColor::Lightest => palette.iter().max_by(by_luminance).map(|entry| entry.color),
+            DuotoneColor::Accent => accent_color(palette),
+            DuotoneColor::Fixed(color) => Some(*color),
+        };
+
+        color.unwrap_or(Rgb([128, 128, 128]))
+    }
+}
+
+impl FromStr for DuotoneColor {
+    type Err = ParseFilterError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "darkest" => Ok(DuotoneColor::Darkest),
+            "lightest" => Ok(DuotoneColor::Lightest),
+            "accent" => Ok(DuotoneColor::Accent),
+            _ => hex_color::parse(s).map(DuotoneColor::Fixed).ok_or_else(|| {
+                ParseFilterError(format!(
+                    "invalid duotone colour `{s}`, expected `darkest`, `lightest`, `accent` or `#RRGGBB`"
+                ))
+            }),
+        }
+    }
+}
+
+/// Applies `filters` to `image` in order.
+pub fn apply_filters(image: &mut RgbImage, filters: &[Filter]) {
+    for filter in filters {
+        match *filter {
+            Filter::Blur(radius) => blur(image, radius),
+            Filter::Brightness(factor) => brightness(image, factor),
+            Filter::Desaturate(amount) => desaturate(image, amount),
+            Filter::Duotone(colors) => duotone(image, &colors),
+            Filter::Vignette(strength) => vignette(image, strength),
+            Filter::Grain(amount) => grain(image, amount),
+        }
+    }
+}
+
+fn blur(image: &mut RgbImage, radius: f64) {
+    let sigma = (shorter_side(image.dimensions()) as f64 * radius) as f32;
+    if sigma <= 0.0 {
+        return;
+    }
+
+    *image = imageops::blur(image, sigma);
+}
+
+fn brightness(image: &mut RgbImage, factor: f64) {
+    for pixel in image.pixels_mut() {
+        for channel in &mut pixel.0 {
+            *channel = (*channel as f64 * factor).round().min(255.0) as u8;
+        }
+    }
+}
+
+fn desaturate(image: &mut RgbImage, amount: f64) {
+    for pixel in image.pixels_mut() {
+        let luma = luma(*pixel);
+        *pixel = color::mix(*pixel, Rgb([luma; 3]), amount);
+    }
+}
+
+fn duotone(image: &mut RgbImage, duotone: &Duotone) {
+    const PALETTE_SIZE: usize = 8;
+    let palette = extract_palette(image, PALETTE_SIZE);
+    let shadows = duotone.shadows.resolve(&palette);
+    let highlights = duotone.highlights.resolve(&palette);
+
+    // the photo's darkest pixels get the shadow colour and its lightest the highlight colour, even
+    // when it isn't very contrasty
+    let (min_luma, max_luma) = image
+        .pixels()
+        .fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+            let luma = luma(*pixel);
+            (min.min(luma), max.max(luma))
+        });
+    let luma_range = (max_luma - min_luma).max(1) as f64;
+
+    for pixel in image.pixels_mut() {
+        let amount = (luma(*pixel) - min_luma) as f64 / luma_range;
+        *pixel = color::mix(shadows, highlights, amount);
+    }
+}
+
+fn vignette(image: &mut RgbImage, strength: f64) {
+    let (width, height) = image.dimensions();
+    let center = (width as f64 / 2.0, height as f64 / 2.0);
+
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        // 0 at the centre and 1 at the corners
+        let distance_squared = ((x as f64 + 0.5 - center.0) / center.0).powi(2) / 2.0
+            + ((y as f64 + 0.5 - center.1) / center.1).powi(2) / 2.0;
+        let factor = 1.0 - strength * distance_squared;
+
+        for channel in &mut pixel.0 {
+            *channel = (*channel as f64 * factor).round() as u8;
+        }
+    }
+}
+
+fn grain(image: &mut RgbImage, amount: f64) {
+    for (x, y, pixel) in image.enumerate_pixels_mut() {
+        // the same noise every time, so a card can be rendered again identically
+        let noise = (noise(x, y) * 2.0 - 1.0) * amount * 255.0;
+
+        for channel in &mut pixel.0 {
+            *channel = (*channel as f64 + noise).round().clamp(0.0, 255.0) as u8;
+        }
+    }
+}
+
+/// A pseudo-random number from 0 to 1 for each pixel.
+fn noise(x: u32, y: u32) -> f64 {
+    let mut hash = (x as u64) << 32 | y as u64;
+    // splitmix64's finaliser
+    hash ^= hash >> 30;
+    hash = hash.wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    hash ^= hash >> 27;
+    hash = hash.wrapping_mul(0x94D0_49BB_1331_11EB);
+    hash ^= hash >> 31;
+
+    (hash >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// The perceived brightness of a colour, from 0 to 255.
+fn luma(Rgb([r, g, b]): Rgb<u8>) -> u8 {
+    (0.2126 * r as f64 + 0.7152 * g as f64 + 0.0722 * b as f64).round() as u8
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn filters_without_a_value_get_a_default() {
+        assert_eq!(
+            Filter::parse_list("blur,brightness,desaturate,vignette,grain,duotone").unwrap(),
+            [
+                Filter::Blur(0.005),
+                Filter::Brightness(0.7),
+                Filter::Desaturate(1.0),
+                Filter::Vignette(0.5),
+                Filter::Grain(0.1),
+                Filter::Duotone(Duotone::default()),
+            ]
+        );
+    }
+
+    #[test]
+    fn filters_keep_their_order_and_values() {
+        assert_eq!(
+            Filter::parse_list(" Blur:0.01, brightness:1.5 ,GRAIN:0").unwrap(),
+            [
+                Filter::Blur(0.01),
+                Filter::Brightness(1.5),
+                Filter::Grain(0.0)
+            ]
+        );
+    }
+
+    #[test]
+    fn none_and_empty_lists_have_no_filters() {
+        for list in ["none", "NONE", "", "  ", ",", " , ,"] {
+            assert_eq!(Filter::parse_list(list).unwrap(), [], "`{list}`");
+        }
+    }
+
+    #[test]
+    fn empty_entries_are_skipped() {
+        assert_eq!(Filter::parse_list("grain,").unwrap(), [Filter::Grain(0.1)]);
+        assert_eq!(
+            Filter::parse_list("blur:0.02,,grain:0.3,").unwrap(),
+            [Filter::Blur(0.02), Filter::Grain(0.3)]
+        );
+    }
+
+    #[test]
+    fn duotone_colours_are_parsed() {
+        assert_eq!(
+            "duotone:lightest:#FF8000".parse::<Filter>().unwrap(),
+            Filter::Duotone(Duotone {
+                shadows: DuotoneColor::Lightest,
+                highlights: DuotoneColor::Fixed(Rgb([255, 128, 0])),
+            })
+        );
+        assert_eq!(
+            "duotone:#102030:Accent".parse::<Filter>().unwrap(),
+            Filter::Duotone(Duotone {
+                shadows: DuotoneColor::Fixed(Rgb([16, 32, 48])),
+                highlights: DuotoneColor::Accent,
+            })
+        );
+    }
+
+    #[test]
+    fn invalid_duotones_are_rejected() {
+        for filter in [
+            "duotone:darkest",
+            "duotone:darkest:lightest:accent",
+            "duotone:darkest:FF8000",
+            "duotone:darkest:#FF80",
+            "duotone:darkest:#GG8000",
+            "duotone:brightest:accent",
+        ] {
+            assert!(filter.parse::<Filter>().is_err(), "`{filter}` was accepted");
+        }
+    }
+
+    #[test]
+    fn out_of_range_values_are_rejected() {
+        for filter in [
+            "blur:-0.01",
+            "blur:0.2",
+            "brightness:2.5",
+            "desaturate:1.5",
+            "vignette:-1",
+            "grain:2",
+            "grain:NaN",
+            "brightness:inf",
+        ] {
+            assert!(filter.parse::<Filter>().is_err(), "`{filter}` was accepted");
+        }
+
+        for filter in [
+            "blur:0",
+            "blur:0.1",
+            "brightness:0",
+            "brightness:2",
+            "grain:1",
+        ] {
+            assert!(filter.parse::<Filter>().is_ok(), "`{filter}` was rejected");
+        }
+    }
+
+    #[test]
+    fn malformed_values_are_rejected() {
+        for filter in ["blur:a lot", "blur:", "grain:0.1:0.2"] {
+            assert!(filter.parse::<Filter>().is_err(), "`{filter}` was accepted");
+        }
+    }
+
+    #[test]
+    fn unknown_filters_are_rejected() {
+        let err = Filter::parse_list("blur,sepia").unwrap_err();
+
+        assert!(err.to_string().contains("unknown filter `sepia`"), "{err}");
+    }
+}
@@ -0,0 +1,96 @@
+use std::{fmt, str::FromStr};
+
+use serde::{Deserialize, Serialize};
+
+/// The shape and size of the rendered card.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Format {
+    /// 3:2, like a photo.
+    #[default]
+    Landscape,
+    /// 1:1.
+    Square,
+    /// 4:5, the tallest most feeds show in full.
+    Portrait,
+    /// 9:16, filling a phone screen.
+    Story,
+    /// 16:9.
+    Wide,
+    /// 3:1, for profile headers.
+    Banner,
+}
+
+impl Format {
+    pub const ALL: [Format; 6] = [
+        Format::Landscape,
+        Format::Square,
+        Format::Portrait,
+        Format::Story,
+        Format::Wide,
+        Format::Banner,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Format::Landscape => "landscape",
+            Format::Square => "square",
+            Format::Portrait => "portrait",
+            Format::Story => "story",
+            Format::Wide => "wide",
+            Format::Banner => "banner",
+        }
+    }
+
+    /// The width and height of the card, in pixels.
+    pub fn dimensions(&self) -> (u32, u32) {
+        match self {
+            Format::Landscape => (1620, 1080),
+            Format::Square => (1080, 1080),
+            Format::Portrait => (1080, 1350),
+            Format::Story => (1080, 1920),
+            Format::Wide => (1920, 1080),
+            Format::Banner => (1500, 500),
+        }
+    }
+
+    /// The ratio of the card's width to its height, in lowest terms.
+    pub fn aspect_ratio(&self) -> (u32, u32) {
+        match self {
+            Format::Landscape => (3, 2),
+            Format::Square => (1, 1),
+            Format::Portrait => (4, 5),
+            Format::Story => (9, 16),
+            Format::Wide => (16, 9),
+            Format::Banner => (3, 1),
+        }
+    }
+}
+
+impl fmt::Display for Format {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseFormatError(String);
+
+impl fmt::Display for ParseFormatError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown format '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseFormatError {}
+
+impl FromStr for Format {
+    type Err = ParseFormatError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Format::ALL
+            .into_iter()
+            .find(|format| format.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseFormatError(String::from(s)))
+    }
+}
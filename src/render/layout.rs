@@ -0,0 +1,407 @@
+use std::{fmt, str::FromStr};
+
+use image::{Pixel, Rgb, Rgba};
+use serde::{Deserialize, Serialize};
+
+use super::{
+    color::Scheme, shorter_side, Alignment, Arrangement, AttributionBox, BoxStyle, Card,
+    HorizontalAlignment, QuoteBox, Theme, Tint, VerticalAlignment,
+};
+
+/// How the quote and attribution are arranged on the card.
+#[derive(Default, Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum Layout {
+    /// A centred quote box with the attribution box underneath.
+    #[default]
+    Classic,
+    /// A left-aligned column of text towards the left of the card.
+    Editorial,
+    /// A band across the bottom third of the card holding both quote and attribution.
+    Caption,
+    /// Large text spanning the whole card over a full-bleed tint.
+    Poster,
+}
+
+impl Layout {
+    pub const ALL: [Layout; 4] = [
+        Layout::Classic,
+        Layout::Editorial,
+        Layout::Caption,
+        Layout::Poster,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Layout::Classic => "classic",
+            Layout::Editorial => "editorial",
+            Layout::Caption => "caption",
+            Layout::Poster => "poster",
+        }
+    }
+
+    /// Lays out a card of `dimensions`, fitting its text into place.
+    pub(super) fn arrange<'a>(
+        &self,
+        dimensions: (u32, u32),
+        card: &Card<'a>,
+        theme: &Theme,
+        scheme: Scheme,
+    ) -> Arrangement<'a> {
+        match self {
+            Layout::Classic => arrange_classic(dimensions, card, theme, scheme),
+            Layout::Editorial => arrange_editorial(dimensions, card, theme, scheme),
+            Layout::Caption => arrange_caption(dimensions, card, theme, scheme),
+            Layout::Poster => arrange_poster(dimensions, card, theme, scheme),
+        }
+    }
+}
+
+impl fmt::Display for Layout {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseLayoutError(String);
+
+impl fmt::Display for ParseLayoutError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "unknown layout '{}'", self.0)
+    }
+}
+
+impl std::error::Error for ParseLayoutError {}
+
+impl FromStr for Layout {
+    type Err = ParseLayoutError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        Layout::ALL
+            .into_iter()
+            .find(|layout| layout.name().eq_ignore_ascii_case(s))
+            .ok_or_else(|| ParseLayoutError(String::from(s)))
+    }
+}
+
+const TRANSPARENT: Rgba<u8> = Rgba([0, 0, 0, 0]);
+
+fn arrange_classic<'a>(
+    dimensions: (u32, u32),
+    card: &Card<'a>,
+    theme: &Theme,
+    scheme: Scheme,
+) -> Arrangement<'a> {
+    const MARGIN_MULTIPLIER: f64 = 0.2;
+    let margin_size = (shorter_side(dimensions) as f64 * MARGIN_MULTIPLIER) as u32;
+
+    const QUOTE_BOX_HEIGHT_MULTIPLIER: f64 = 3.0 / 4.0;
+
+    let max_quote_box_dimensions = (
+        dimensions.0 - margin_size * 2,
+        ((dimensions.1 - margin_size * 2) as f64 * QUOTE_BOX_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_quote_box_position = (margin_size, margin_size);
+
+    let quote = QuoteBox::new(
+        card,
+        &boxed(
+            scheme,
+            theme,
+            dimensions,
+            theme.length(theme.quote_padding, dimensions),
+            Alignment::CENTER,
+        ),
+        max_quote_box_position,
+        max_quote_box_dimensions,
+    );
+
+    const BOX_GAP_MULTIPLIER: f64 = 0.025;
+    let box_gap_size = (shorter_side(dimensions) as f64 * BOX_GAP_MULTIPLIER) as u32;
+
+    const ATTRIBUTION_BOX_HEIGHT_MULTIPLIER: f64 = 1.0 / 7.0;
+
+    let max_attribution_box_dimensions = (
+        max_quote_box_dimensions.0,
+        ((dimensions.1 - margin_size * 2) as f64 * ATTRIBUTION_BOX_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_attribution_box_position = (
+        max_quote_box_position.0,
+        (max_quote_box_position.1 + max_quote_box_dimensions.1) + box_gap_size,
+    );
+
+    let attribution = AttributionBox::new(
+        card,
+        &theme.date_format,
+        &boxed(
+            scheme,
+            theme,
+            dimensions,
+            theme.length(theme.attribution_padding, dimensions),
+            Alignment::CENTER,
+        ),
+        max_attribution_box_position,
+        max_attribution_box_dimensions,
+    );
+
+    Arrangement::new(None, quote, attribution)
+}
+
+fn arrange_editorial<'a>(
+    dimensions: (u32, u32),
+    card: &Card<'a>,
+    theme: &Theme,
+    scheme: Scheme,
+) -> Arrangement<'a> {
+    const MARGIN_MULTIPLIER: f64 = 0.1;
+    let margin_size = (shorter_side(dimensions) as f64 * MARGIN_MULTIPLIER) as u32;
+
+    const COLUMN_WIDTH_MULTIPLIER: f64 = 0.6;
+    // on cards that aren't wider than they're tall, the column spans the whole card
+    let column_width = if dimensions.0 > dimensions.1 {
+        (dimensions.0 as f64 * COLUMN_WIDTH_MULTIPLIER) as u32 - margin_size
+    } else {
+        dimensions.0 - margin_size * 2
+    };
+    let column_height = dimensions.1 - margin_size * 2;
+
+    // the narrower column gets slightly tighter padding than the theme asks for
+    const PADDING_MULTIPLIER: f64 = 0.8;
+
+    const QUOTE_BOX_HEIGHT_MULTIPLIER: f64 = 3.0 / 4.0;
+
+    // the quote sits at the bottom of its space so it hugs the attribution underneath
+    let max_quote_box_dimensions = (
+        column_width,
+        (column_height as f64 * QUOTE_BOX_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_quote_box_position = (margin_size, margin_size);
+
+    let quote = QuoteBox::new(
+        card,
+        &boxed(
+            scheme,
+            theme,
+            dimensions,
+            theme.length(theme.quote_padding * PADDING_MULTIPLIER, dimensions),
+            Alignment {
+                horizontal: HorizontalAlignment::Left,
+                vertical: VerticalAlignment::Bottom,
+            },
+        ),
+        max_quote_box_position,
+        max_quote_box_dimensions,
+    );
+
+    const BOX_GAP_MULTIPLIER: f64 = 0.02;
+    let box_gap_size = (shorter_side(dimensions) as f64 * BOX_GAP_MULTIPLIER) as u32;
+
+    const ATTRIBUTION_BOX_HEIGHT_MULTIPLIER: f64 = 1.0 / 8.0;
+
+    let max_attribution_box_dimensions = (
+        (column_width as f64 * 0.75) as u32,
+        (column_height as f64 * ATTRIBUTION_BOX_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_attribution_box_position = (
+        max_quote_box_position.0,
+        max_quote_box_position.1 + max_quote_box_dimensions.1 + box_gap_size,
+    );
+
+    let attribution = AttributionBox::new(
+        card,
+        &theme.date_format,
+        &boxed(
+            scheme,
+            theme,
+            dimensions,
+            theme.length(theme.attribution_padding * PADDING_MULTIPLIER, dimensions),
+            Alignment {
+                horizontal: HorizontalAlignment::Left,
+                vertical: VerticalAlignment::Top,
+            },
+        ),
+        max_attribution_box_position,
+        max_attribution_box_dimensions,
+    );
+
+    Arrangement::new(None, quote, attribution)
+}
+
+fn arrange_caption<'a>(
+    dimensions: (u32, u32),
+    card: &Card<'a>,
+    theme: &Theme,
+    scheme: Scheme,
+) -> Arrangement<'a> {
+    const BAND_HEIGHT_MULTIPLIER: f64 = 1.0 / 3.0;
+    let band_dimensions = (
+        dimensions.0,
+        (dimensions.1 as f64 * BAND_HEIGHT_MULTIPLIER) as u32,
+    );
+    let band_position = (0, dimensions.1 - band_dimensions.1);
+
+    let band = Tint {
+        color: scheme.box_color,
+        position: band_position,
+        dimensions: band_dimensions,
+    };
+
+    const MARGIN_MULTIPLIER: f64 = 0.05;
+    let margin_size = (dimensions.0 as f64 * MARGIN_MULTIPLIER) as u32;
+
+    const BAND_PADDING_MULTIPLIER: f64 = 0.1;
+    let band_padding_size = (band_dimensions.1 as f64 * BAND_PADDING_MULTIPLIER) as u32;
+
+    let content_dimensions = (
+        band_dimensions.0 - margin_size * 2,
+        band_dimensions.1 - band_padding_size * 2,
+    );
+
+    const QUOTE_HEIGHT_MULTIPLIER: f64 = 0.7;
+    const ATTRIBUTION_HEIGHT_MULTIPLIER: f64 = 0.22;
+
+    let max_quote_box_dimensions = (
+        content_dimensions.0,
+        (content_dimensions.1 as f64 * QUOTE_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_quote_box_position = (margin_size, band_position.1 + band_padding_size);
+
+    let quote = QuoteBox::new(
+        card,
+        &unboxed(scheme.text_color, theme, dimensions),
+        max_quote_box_position,
+        max_quote_box_dimensions,
+    );
+
+    let max_attribution_box_dimensions = (
+        content_dimensions.0,
+        (content_dimensions.1 as f64 * ATTRIBUTION_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_attribution_box_position = (
+        margin_size,
+        band_position.1 + band_padding_size + content_dimensions.1
+            - max_attribution_box_dimensions.1,
+    );
+
+    let attribution = AttributionBox::new(
+        card,
+        &theme.date_format,
+        &unboxed(scheme.text_color, theme, dimensions),
+        max_attribution_box_position,
+        max_attribution_box_dimensions,
+    );
+
+    Arrangement::new(Some(band), quote, attribution)
+}
+
+fn arrange_poster<'a>(
+    dimensions: (u32, u32),
+    card: &Card<'a>,
+    theme: &Theme,
+    scheme: Scheme,
+) -> Arrangement<'a> {
+    let tint = Tint {
+        color: scheme.box_color,
+        position: (0, 0),
+        dimensions,
+    };
+
+    const MARGIN_MULTIPLIER: f64 = 0.08;
+    let margin_size = (shorter_side(dimensions) as f64 * MARGIN_MULTIPLIER) as u32;
+
+    let content_dimensions = (
+        dimensions.0 - margin_size * 2,
+        dimensions.1 - margin_size * 2,
+    );
+
+    const QUOTE_HEIGHT_MULTIPLIER: f64 = 0.8;
+    const ATTRIBUTION_HEIGHT_MULTIPLIER: f64 = 0.1;
+
+    let max_quote_box_dimensions = (
+        content_dimensions.0,
+        (content_dimensions.1 as f64 * QUOTE_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_quote_box_position = (margin_size, margin_size);
+
+    let quote = QuoteBox::new(
+        card,
+        &unboxed(scheme.text_color, theme, dimensions),
+        max_quote_box_position,
+        max_quote_box_dimensions,
+    );
+
+    let max_attribution_box_dimensions = (
+        content_dimensions.0,
+        (content_dimensions.1 as f64 * ATTRIBUTION_HEIGHT_MULTIPLIER) as u32,
+    );
+    let max_attribution_box_position = (
+        margin_size,
+        dimensions.1 - margin_size - max_attribution_box_dimensions.1,
+    );
+
+    let attribution = AttributionBox::new(
+        card,
+        &theme.date_format,
+        &unboxed(scheme.text_color, theme, dimensions),
+        max_attribution_box_position,
+        max_attribution_box_dimensions,
+    );
+
+    Arrangement::new(Some(tint), quote, attribution)
+}
+
+/// A box drawn as the theme describes.
+fn boxed(
+    scheme: Scheme,
+    theme: &Theme,
+    card_dimensions: (u32, u32),
+    padding_size: u32,
+    alignment: Alignment,
+) -> BoxStyle {
+    BoxStyle {
+        text_color: scheme.text_color.to_rgba(),
+        box_color: scheme.box_color,
+        corner_radius: theme.length(theme.corner_radius, card_dimensions),
+        padding_size,
+        alignment,
+        balance_lines: theme.balance_lines,
+        min_font_size: theme.length(theme.min_font_size, card_dimensions),
+        backdrop_blur: theme.length(theme.backdrop_blur, card_dimensions),
+        border_width: theme.length(theme.border_width, card_dimensions),
+        border_color: theme.border_rgba(),
+        shadow_color: theme.shadow_rgba(),
+        shadow_blur: theme.length(theme.shadow_blur, card_dimensions),
+        shadow_offset: theme.length(theme.shadow_offset, card_dimensions),
+        text_outline_width: theme.length(theme.text_outline_width, card_dimensions),
+        text_outline_color: theme.text_outline_rgba(),
+        text_shadow_color: theme.text_shadow_rgba(),
+        text_shadow_blur: theme.length(theme.text_shadow_blur, card_dimensions),
+        text_shadow_offset: theme.length(theme.text_shadow_offset, card_dimensions),
+    }
+}
+
+/// Centred text drawn straight onto the card, for layouts that provide their own backdrop. The
+/// theme's text outline and shadow are still drawn.
+fn unboxed(text_color: Rgb<u8>, theme: &Theme, card_dimensions: (u32, u32)) -> BoxStyle {
+    BoxStyle {
+        text_color: text_color.to_rgba(),
+        box_color: TRANSPARENT,
+        corner_radius: 0,
+        padding_size: 0,
+        alignment: Alignment::CENTER,
+        balance_lines: theme.balance_lines,
+        min_font_size: theme.length(theme.min_font_size, card_dimensions),
+        backdrop_blur: 0,
+        border_width: 0,
+        border_color: TRANSPARENT,
+        shadow_color: TRANSPARENT,
+        shadow_blur: 0,
+        shadow_offset: 0,
+        text_outline_width: theme.length(theme.text_outline_width, card_dimensions),
+        text_outline_color: theme.text_outline_rgba(),
+        text_shadow_color: theme.text_shadow_rgba(),
+        text_shadow_blur: theme.length(theme.text_shadow_blur, card_dimensions),
+        text_shadow_offset: theme.length(theme.text_shadow_offset, card_dimensions),
+    }
+}
@@ -0,0 +1,414 @@
+use std::{collections::HashMap, ops::Range, sync::OnceLock};
+
+use hyphenation::{Hyphenator, Language, Load, Standard};
+use rusttype::Scale;
+use unicode_linebreak::BreakOpportunity;
+use unicode_segmentation::UnicodeSegmentation;
+
+use super::{rich_text::RichText, Typesetter};
+
+// when balancing, ending a line with a hyphen costs about as much as leaving a third of it empty
+const HYPHEN_COST: f64 = 0.1;
+
+static HYPHENATOR: OnceLock<Standard> = OnceLock::new();
+
+/// A stretch of text that's never broken across lines.
+struct Piece {
+    range: Range<usize>,
+    /// Whether a line ending after this piece gets a hyphen.
+    hyphenated: bool,
+    /// Whether a line has to end after this piece.
+    mandatory_break: bool,
+}
+
+/// Breaks text into lines no wider than `max_width` wherever the Unicode line breaking rules allow.
+/// With `split_words` set, words too wide for a line of their own are hyphenated, or failing that
+/// split between grapheme clusters. Otherwise they're left to overflow.
+///
+/// With `balance` set, lines are evened out without adding any more of them, so the last line
+/// isn't left holding a lone word.
+pub fn wrap_text(
+    text: &RichText,
+    typesetter: &Typesetter,
+    scale: Scale,
+    max_width: u32,
+    balance: bool,
+    split_words: bool,
+) -> Vec<RichText> {
+    let mut line_breaker = LineBreaker {
+        text,
+        typesetter,
+        scale,
+        max_width,
+        split_words,
+        widths: HashMap::new(),
+    };
+
+    let pieces = line_breaker.pieces();
+    if pieces.is_empty() {
+        return vec![RichText::default()];
+    }
+
+    let mut line_ends = line_breaker.greedy_line_ends(&pieces);
+    if balance && line_ends.len() > 1 {
+        line_ends = line_breaker.balanced_line_ends(&pieces, line_ends.len());
+    }
+
+    let mut line_start = 0;
+    line_ends
+        .into_iter()
+        .map(|line_end| {
+            let line = line_breaker.line(&pieces[line_start..line_end]);
+            line_start = line_end;
+
+            line
+        })
+        .collect()
+}
+
+struct LineBreaker<'a, 'b> {
+    text: &'a RichText,
+    typesetter: &'a Typesetter<'b>,
+    scale: Scale,
+    max_width: u32,
+    split_words: bool,
+    /// Measured widths of lines, keyed by the range of pieces on them.
+    widths: HashMap<(usize, usize), u32>,
+}
+
+impl LineBreaker<'_, '_> {
+    /// Splits the text at every break opportunity, then splits up any piece too wide to fit on a
+    /// line by itself if words can be split.
+    fn pieces(&self) -> Vec<Piece> {
+        let mut pieces = Vec::new();
+
+        let mut piece_start = 0;
+        for (piece_end, opportunity) in unicode_linebreak::linebreaks(self.text.as_str()) {
+            self.push_pieces(
+                &mut pieces,
+                piece_start..piece_end,
+                opportunity == BreakOpportunity::Mandatory,
+            );
+            piece_start = piece_end;
+        }
+
+        pieces
+    }
+
+    fn push_pieces(&self, pieces: &mut Vec<Piece>, range: Range<usize>, mandatory_break: bool) {
+        let text = self.text.as_str();
+        let mut piece_start = range.start;
+
+        while self.split_words && self.width(piece_start..range.end, false) > self.max_width {
+            let word_end = piece_start + text[piece_start..range.end].trim_end().len();
+
+            let (piece_end, hyphenated) = match self.hyphenation_point(piece_start..word_end) {
+                Some(piece_end) => (piece_end, true),
+                None => (self.grapheme_split_point(piece_start..word_end), false),
+            };
+            if piece_end >= word_end {
+                break;
+            }
+
+            pieces.push(Piece {
+                range: piece_start..piece_end,
+                hyphenated,
+                mandatory_break: false,
+            });
+            piece_start = piece_end;
+        }
+
+        pieces.push(Piece {
+            range: piece_start..range.end,
+            hyphenated: false,
+            mandatory_break,
+        });
+    }
+
+    /// The last place `word` can be hyphenated with the first part still fitting on a line.
+    fn hyphenation_point(&self, word: Range<usize>) -> Option<usize> {
+        let hyphenator = HYPHENATOR.get_or_init(|| {
+            Standard::from_embedded(Language::EnglishUS)
+                .expect("embedded hyphenation dictionary should load")
+        });
+
+        hyphenator
+            .hyphenate(&self.text.as_str()[word.clone()])
+            .breaks
+            .into_iter()
+            .rev()
+            .map(|index| word.start + index)
+            .find(|index| self.width(word.start..*index, true) <= self.max_width)
+    }
+
+    /// The last grapheme boundary in `word` with the text before it still fitting on a line,
+    /// taking at least one grapheme cluster so the split always makes progress.
+    fn grapheme_split_point(&self, word: Range<usize>) -> usize {
+        let word_text = &self.text.as_str()[word.clone()];
+
+        let mut split_point = word.start
+            + word_text
+                .graphemes(true)
+                .next()
+                .map(str::len)
+                .unwrap_or_default();
+        for (index, grapheme) in word_text.grapheme_indices(true).skip(1) {
+            let end = word.start + index + grapheme.len();
+            if self.width(word.start..end, false) > self.max_width {
+                break;
+            }
+
+            split_point = end;
+        }
+
+        split_point
+    }
+
+    /// Fits as many pieces on each line as possible, returning where each line ends.
+    fn greedy_line_ends(&mut self, pieces: &[Piece]) -> Vec<usize> {
+        let mut line_ends = Vec::new();
+
+        let mut line_start = 0;
+        while line_start < pieces.len() {
+            let mut line_end = line_start + 1;
+            while line_end < pieces.len()
+                && !pieces[line_end - 1].mandatory_break
+                && self.line_width(pieces, line_start, line_end + 1) <= self.max_width
+            {
+                line_end += 1;
+            }
+
+            line_ends.push(line_end);
+            line_start = line_end;
+        }
+
+        line_ends
+    }
+
+    /// Splits the pieces over `line_count` lines, keeping the space left at the end of each line
+    /// as even as possible, and returning where each line ends.
+    fn balanced_line_ends(&mut self, pieces: &[Piece], line_count: usize) -> Vec<usize> {
+        // costs[lines][end] is the cost of the best way of fitting the first `end` pieces on
+        // `lines` lines, and where the last of those lines starts
+        let mut costs = vec![vec![None; pieces.len() + 1]; line_count + 1];
+        costs[0][0] = Some((0.0, 0));
+
+        for lines in 1..=line_count {
+            for line_end in 1..=pieces.len() {
+                for line_start in (0..line_end).rev() {
+                    if line_start + 1 < line_end && pieces[line_start].mandatory_break {
+                        break;
+                    }
+
+                    let width = self.line_width(pieces, line_start, line_end);
+                    if line_start + 1 < line_end && width > self.max_width {
+                        break;
+                    }
+
+                    let Some((previous_cost, _)) = costs[lines - 1][line_start] else {
+                        continue;
+                    };
+
+                    let slack = self.max_width.saturating_sub(width) as f64 / self.max_width as f64;
+                    let mut cost = previous_cost + slack * slack;
+                    if pieces[line_end - 1].hyphenated {
+                        cost += HYPHEN_COST;
+                    }
+
+                    if costs[lines][line_end].is_none_or(|(best_cost, _)| cost < best_cost) {
+                        costs[lines][line_end] = Some((cost, line_start));
+                    }
+                }
+            }
+        }
+
+        let mut line_ends = Vec::with_capacity(line_count);
+        let mut line_end = pieces.len();
+        for lines in (1..=line_count).rev() {
+            line_ends.push(line_end);
+            let (_, line_start) =
+                costs[lines][line_end].expect("greedy line breaks should always be possible");
+            line_end = line_start;
+        }
+        line_ends.reverse();
+
+        line_ends
+    }
+
+    fn line(&self, pieces: &[Piece]) -> RichText {
+        let (first, last) = (&pieces[0], &pieces[pieces.len() - 1]);
+
+        self.trimmed_slice(first.range.start..last.range.end, last.hyphenated)
+    }
+
+    fn line_width(&mut self, pieces: &[Piece], start: usize, end: usize) -> u32 {
+        if let Some(width) = self.widths.get(&(start, end)) {
+            return *width;
+        }
+
+        let range = pieces[start].range.start..pieces[end - 1].range.end;
+        let width = self.width(range, pieces[end - 1].hyphenated);
+        self.widths.insert((start, end), width);
+
+        width
+    }
+
+    fn width(&self, range: Range<usize>, hyphenated: bool) -> u32 {
+        let text = self.trimmed_slice(range, hyphenated);
+
+        self.typesetter.text_size(self.scale, &text).0.max(0) as u32
+    }
+
+    /// The text in `range` without any trailing whitespace, and with a hyphen if `hyphenated`.
+    fn trimmed_slice(&self, range: Range<usize>, hyphenated: bool) -> RichText {
+        let end = range.start + self.text.as_str()[range.clone()].trim_end().len();
+
+        let mut text = self.text.slice(range.start..end);
+        if hyphenated {
+            text.push_str("-");
+        }
+
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        assets::fonts::FontFamily, custom_emoji::CustomEmojiImages, render::bidi::Direction,
+    };
+
+    const SCALE: Scale = Scale { x: 20.0, y: 20.0 };
+
+    fn typesetter(custom_emoji: &CustomEmojiImages) -> Typesetter<'_> {
+        Typesetter {
+            fonts: FontFamily::quote(),
+            custom_emoji,
+            direction: Direction::LeftToRight,
+        }
+    }
+
+    fn wrap(text: &str, max_width: u32, balance: bool, split_words: bool) -> Vec<String> {
+        let custom_emoji = CustomEmojiImages::new();
+
+        wrap_text(
+            &RichText::plain(text),
+            &typesetter(&custom_emoji),
+            SCALE,
+            max_width,
+            balance,
+            split_words,
+        )
+        .iter()
+        .map(|line| String::from(line.as_str()))
+        .collect()
+    }
+
+    fn width(text: &str) -> u32 {
+        let custom_emoji = CustomEmojiImages::new();
+
+        typesetter(&custom_emoji)
+            .text_size(SCALE, &RichText::plain(text))
+            .0 as u32
+    }
+
+    #[test]
+    fn grapheme_clusters_are_never_split() {
+        // a family emoji joined with zero width joiners, and letters with combining accents
+        for text in [
+            "\u{1F468}\u{200D}\u{1F469}\u{200D}\u{1F467}\u{200D}\u{1F466}".repeat(3),
+            "a\u{301}e\u{301}\u{323}o\u{308}u\u{302}".to_owned(),
+        ] {
+            let lines = wrap(&text, 1, false, true);
+
+            assert_eq!(lines, text.graphemes(true).collect::<Vec<_>>());
+        }
+    }
+
+    #[test]
+    fn words_too_long_for_a_line_are_hyphenated() {
+        let word = "incomprehensibilities";
+        let max_width = width(word) / 2;
+
+        let lines = wrap(word, max_width, false, true);
+
+        assert!(lines.len() > 1);
+        assert!(lines[0].ends_with('-'), "{lines:?} isn't hyphenated");
+        for line in &lines {
+            assert!(width(line) <= max_width, "`{line}` overflows");
+        }
+        assert_eq!(
+            lines
+                .iter()
+                .map(|line| line.trim_end_matches('-'))
+                .collect::<String>(),
+            word
+        );
+    }
+
+    #[test]
+    fn words_that_cant_be_hyphenated_are_split() {
+        let word = "zzzzzzzzzzzzzzzzzzzz";
+        let max_width = width(word) / 3;
+
+        let lines = wrap(word, max_width, false, true);
+
+        assert!(lines.len() >= 3);
+        for line in &lines {
+            assert!(width(line) <= max_width, "`{line}` overflows");
+        }
+        assert_eq!(lines.concat(), word);
+    }
+
+    #[test]
+    fn long_words_overflow_unless_they_can_be_split() {
+        let word = "incomprehensibilities";
+
+        assert_eq!(wrap(word, width(word) / 2, false, false), [word]);
+    }
+
+    #[test]
+    fn newlines_force_a_break() {
+        let text = "first\nsecond third";
+
+        assert_eq!(
+            wrap(text, width(text) * 2, false, false),
+            ["first", "second third"]
+        );
+        assert_eq!(
+            wrap(text, width(text) * 2, true, false),
+            ["first", "second third"]
+        );
+    }
+
+    #[test]
+    fn balancing_never_adds_lines() {
+        let text = "The quick brown fox jumps over the lazy dog, and then it keeps running \
+            until it's well out of sight of the farmhouse.";
+
+        for max_width in (60..=400).step_by(20) {
+            let greedy = wrap(text, max_width, false, true);
+            let balanced = wrap(text, max_width, true, true);
+
+            assert_eq!(balanced.len(), greedy.len(), "at a width of {max_width}");
+            for line in &balanced {
+                assert!(width(line) <= max_width, "`{line}` overflows {max_width}");
+            }
+        }
+    }
+
+    #[test]
+    fn balancing_evens_out_lines() {
+        let text = "one two three four five six seven eight nine ten eleven";
+        // just too narrow for everything but the last word on the first line
+        let max_width = width("one two three four five six seven eight nine ten") + 1;
+
+        let greedy = wrap(text, max_width, false, false);
+        let balanced = wrap(text, max_width, true, false);
+
+        assert_eq!(greedy.last().map(String::as_str), Some("eleven"));
+        assert_eq!(balanced.len(), 2);
+        assert!(width(&balanced[1]) > width("eleven"));
+    }
+}
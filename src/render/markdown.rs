@@ -0,0 +1,336 @@
+use super::rich_text::{RichText, Style};
+
+/// Markers that wrap styled text, along with the style they apply. Longer markers come first so
+/// `**` is tried before `*`.
+const DELIMITERS: [(&str, ApplyStyle); 5] = [
+    ("**", |style| style.bold = true),
+    ("__", |style| style.underline = true),
+    ("~~", |style| style.strikethrough = true),
+    ("*", |style| style.italic = true),
+    ("_", |style| style.italic = true),
+];
+
+type ApplyStyle = fn(&mut Style);
+
+/// Parses the bold, italic, underline, strikethrough and code spans of Discord's markdown into
+/// styled text. Markers without a partner are drawn as they are.
+pub fn parse(source: &str) -> RichText {
+    let mut rich_text = RichText::default();
+    parse_into(&mut rich_text, source, Style::default());
+
+    rich_text
+}
+
+fn parse_into(rich_text: &mut RichText, source: &str, style: Style) {
+    let mut text_start = 0;
+    let mut index = 0;
+    while let Some(rest) = source.get(index..).filter(|rest| !rest.is_empty()) {
+        let first_char = rest.chars().next().expect("rest should not be empty");
+
+        // escaped punctuation is drawn without its backslash
+        if let Some(escaped) = escaped_char(rest) {
+            rich_text.push(&source[text_start..index], style);
+            text_start = index + 1;
+            index += 1 + escaped.len_utf8();
+            continue;
+        }
+
+        if let Some((code, len)) = code_span(rest) {
+            rich_text.push(&source[text_start..index], style);
+            rich_text.push(
+                code,
+                Style {
+                    code: true,
+                    ..style
+                },
+            );
+            index += len;
+            text_start = index;
+            continue;
+        }
+
+        let delimited = DELIMITERS.iter().find_map(|(delimiter, apply)| {
+            let close_index = closing_delimiter(source, index, delimiter)?;
+
+            Some((delimiter, apply, close_index))
+        });
+        if let Some((delimiter, apply, close_index)) = delimited {
+            rich_text.push(&source[text_start..index], style);
+
+            let mut inner_style = style;
+            apply(&mut inner_style);
+            parse_into(
+                rich_text,
+                &source[index + delimiter.len()..close_index],
+                inner_style,
+            );
+
+            index = close_index + delimiter.len();
+            text_start = index;
+            continue;
+        }
+
+        index += first_char.len_utf8();
+    }
+
+    rich_text.push(&source[text_start..], style);
+}
+
+fn escaped_char(text: &str) -> Option<char> {
+    text.strip_prefix('\\')?
+        .chars()
+        .next()
+        .filter(char::is_ascii_punctuation)
+}
+
+/// Parses a code span or block at the start of `text`, returning its contents and how many bytes
+/// it covers.
+fn code_span(text: &str) -> Option<(&str, usize)> {
+    let fence = ["```", "``", "`"]
+        .into_iter()
+        .find(|fence| text.starts_with(fence))?;
+
+    let content_start = fence.len();
+    let content_len = text[content_start..].find(fence)?;
+    let content = text[content_start..content_start + content_len].trim();
+    if content.is_empty() {
+        return None;
+    }
+
+    Some((content, content_start + content_len + fence.len()))
+}
+
+/// Finds the marker closing one that opens at `open_index`, if there is one.
+fn closing_delimiter(source: &str, open_index: usize, delimiter: &str) -> Option<usize> {
+    if !source[open_index..].starts_with(delimiter) {
+        return None;
+    }
+
+    let delimiter_char = delimiter.chars().next()?;
+    let single = delimiter.len() == 1;
+    let content_start = open_index + delimiter.len();
+
+    let first_content_char = source[content_start..].chars().next()?;
+    if single && first_content_char.is_whitespace() {
+        return None;
+    }
+    // a lone marker left over from a doubled one that didn't close, as in `**a* b`, is drawn as it
+    // is, leaving the next one to open
+    if single
+        && first_content_char == delimiter_char
+        && !source[content_start..].starts_with(&delimiter.repeat(2))
+    {
+        return None;
+    }
+    // underscores inside words, like in snake_case, aren't markup
+    let previous_char = source[..open_index].chars().next_back();
+    if delimiter == "_" && previous_char.is_some_and(char::is_alphanumeric) {
+        return None;
+    }
+
+    let mut index = content_start + first_content_char.len_utf8();
+    while let Some(rest) = source.get(index..).filter(|rest| !rest.is_empty()) {
+        if let Some(escaped) = escaped_char(rest) {
+            index += 1 + escaped.len_utf8();
+            continue;
+        }
+        if let Some((_, len)) = code_span(rest) {
+            index += len;
+            continue;
+        }
+        // doubled markers can sit inside single ones, as in `*a **b** c*`
+        if single && rest.starts_with(&delimiter.repeat(2)) {
+            index += 2 * delimiter.len();
+            continue;
+        }
+
+        if let Some(after) = rest.strip_prefix(delimiter) {
+            let previous_char = source[..index].chars().next_back();
+            let next_char = after.chars().next();
+
+            let closes = next_char != Some(delimiter_char)
+                && !(single && previous_char.is_some_and(char::is_whitespace))
+                && !(delimiter == "_" && next_char.is_some_and(char::is_alphanumeric));
+            if closes {
+                return Some(index);
+            }
+        }
+
+        index += rest.chars().next()?.len_utf8();
+    }
+
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLAIN: Style = Style {
+        bold: false,
+        italic: false,
+        underline: false,
+        strikethrough: false,
+        code: false,
+    };
+    const BOLD: Style = Style {
+        bold: true,
+        ..PLAIN
+    };
+    const ITALIC: Style = Style {
+        italic: true,
+        ..PLAIN
+    };
+    const BOLD_ITALIC: Style = Style {
+        bold: true,
+        italic: true,
+        ..PLAIN
+    };
+    const CODE: Style = Style {
+        code: true,
+        ..PLAIN
+    };
+
+    /// The text of each span `source` is parsed into, along with its style.
+    fn spans(source: &str) -> Vec<(String, Style)> {
+        let rich_text = parse(source);
+
+        rich_text
+            .spans()
+            .iter()
+            .map(|span| {
+                (
+                    rich_text.as_str()[span.range.clone()].to_owned(),
+                    span.style,
+                )
+            })
+            .collect()
+    }
+
+    fn expected(spans: &[(&str, Style)]) -> Vec<(String, Style)> {
+        spans
+            .iter()
+            .map(|(text, style)| (String::from(*text), *style))
+            .collect()
+    }
+
+    #[test]
+    fn plain_text_is_one_span() {
+        assert_eq!(spans("just words"), expected(&[("just words", PLAIN)]));
+    }
+
+    #[test]
+    fn each_marker_applies_its_style() {
+        assert_eq!(
+            spans("a **b** c"),
+            expected(&[("a ", PLAIN), ("b", BOLD), (" c", PLAIN)])
+        );
+        assert_eq!(spans("*a*"), expected(&[("a", ITALIC)]));
+        assert_eq!(spans("_a_"), expected(&[("a", ITALIC)]));
+        assert_eq!(
+            spans("__a__"),
+            expected(&[(
+                "a",
+                Style {
+                    underline: true,
+                    ..PLAIN
+                }
+            )])
+        );
+        assert_eq!(
+            spans("~~a~~"),
+            expected(&[(
+                "a",
+                Style {
+                    strikethrough: true,
+                    ..PLAIN
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn nested_markers_combine_their_styles() {
+        assert_eq!(
+            spans("*a **b** c*"),
+            expected(&[("a ", ITALIC), ("b", BOLD_ITALIC), (" c", ITALIC)])
+        );
+        assert_eq!(
+            spans("__*a*__"),
+            expected(&[(
+                "a",
+                Style {
+                    italic: true,
+                    underline: true,
+                    ..PLAIN
+                }
+            )])
+        );
+    }
+
+    #[test]
+    fn triple_asterisks_are_bold_and_italic() {
+        assert_eq!(
+            spans("a ***b*** c"),
+            expected(&[("a ", PLAIN), ("b", BOLD_ITALIC), (" c", PLAIN)])
+        );
+    }
+
+    #[test]
+    fn escaped_markers_are_drawn_without_their_backslash() {
+        assert_eq!(spans(r"\*a\*"), expected(&[("*a*", PLAIN)]));
+        assert_eq!(spans(r"**a\*\*b**"), expected(&[("a**b", BOLD)]));
+        // only punctuation can be escaped
+        assert_eq!(spans(r"C:\Users"), expected(&[(r"C:\Users", PLAIN)]));
+    }
+
+    #[test]
+    fn underscores_inside_words_are_left_alone() {
+        assert_eq!(
+            spans("snake_case_name"),
+            expected(&[("snake_case_name", PLAIN)])
+        );
+        assert_eq!(
+            spans("call my_function_ now"),
+            expected(&[("call my_function_ now", PLAIN)])
+        );
+        assert_eq!(spans("_a_b_"), expected(&[("a_b", ITALIC)]));
+    }
+
+    #[test]
+    fn code_is_drawn_as_it_is() {
+        assert_eq!(
+            spans("run `**x**` now"),
+            expected(&[("run ", PLAIN), ("**x**", CODE), (" now", PLAIN)])
+        );
+        assert_eq!(spans("```let x = 1;```"), expected(&[("let x = 1;", CODE)]));
+        // a marker doesn't close inside code
+        assert_eq!(
+            spans("*a `*` b*"),
+            expected(&[
+                ("a ", ITALIC),
+                (
+                    "*",
+                    Style {
+                        code: true,
+                        ..ITALIC
+                    }
+                ),
+                (" b", ITALIC)
+            ])
+        );
+    }
+
+    #[test]
+    fn unmatched_markers_are_drawn_as_they_are() {
+        assert_eq!(spans("**unclosed"), expected(&[("**unclosed", PLAIN)]));
+        assert_eq!(spans("a*b"), expected(&[("a*b", PLAIN)]));
+        assert_eq!(spans("2 * 3 * 4"), expected(&[("2 * 3 * 4", PLAIN)]));
+        assert_eq!(spans("`unclosed"), expected(&[("`unclosed", PLAIN)]));
+        assert_eq!(spans("``"), expected(&[("``", PLAIN)]));
+        assert_eq!(
+            spans("**a* b"),
+            expected(&[("*", PLAIN), ("a", ITALIC), (" b", PLAIN)])
+        );
+    }
+}
@@ -0,0 +1,105 @@
+use image::{imageops, Rgb, RgbImage};
+
+// photos are shrunk to fit within this many pixels square before their colours are counted
+const SAMPLE_SIZE: u32 = 64;
+
+/// One of an image's dominant colours.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PaletteColor {
+    pub color: Rgb<u8>,
+    /// The fraction of the image made up of this colour, from 0 to 1.
+    pub weight: f64,
+}
+
+/// Finds up to `count` dominant colours in an image using median cut, most dominant first.
+pub fn extract_palette(image: &RgbImage, count: usize) -> Vec<PaletteColor> {
+    let (width, height) = image.dimensions();
+    if count == 0 || width == 0 || height == 0 {
+        return Vec::new();
+    }
+
+    let scale_factor = (SAMPLE_SIZE as f64 / width.max(height) as f64).min(1.0);
+    let sample = imageops::thumbnail(
+        image,
+        ((width as f64 * scale_factor) as u32).max(1),
+        ((height as f64 * scale_factor) as u32).max(1),
+    );
+
+    let pixels: Vec<[u8; 3]> = sample.pixels().map(|pixel| pixel.0).collect();
+    let pixel_count = pixels.len();
+
+    let mut boxes = vec![pixels];
+    while boxes.len() < count {
+        let Some((index, channel)) = boxes
+            .iter()
+            .enumerate()
+            .filter(|(_, pixels)| pixels.len() > 1)
+            .map(|(index, pixels)| (index, widest_channel(pixels)))
+            .filter(|(_, (_, range))| *range > 0)
+            .max_by_key(|(_, (_, range))| *range)
+            .map(|(index, (channel, _))| (index, channel))
+        else {
+            break;
+        };
+
+        let pixels = &mut boxes[index];
+        pixels.sort_unstable_by_key(|pixel| pixel[channel]);
+        let upper_half = pixels.split_off(pixels.len() / 2);
+        boxes.push(upper_half);
+    }
+
+    let mut palette: Vec<PaletteColor> = boxes
+        .iter()
+        .map(|pixels| {
+            let mut sum = [0u64; 3];
+            for pixel in pixels {
+                for (sum, channel) in sum.iter_mut().zip(pixel) {
+                    *sum += *channel as u64;
+                }
+            }
+
+            PaletteColor {
+                color: Rgb(sum.map(|sum| (sum / pixels.len() as u64) as u8)),
+                weight: pixels.len() as f64 / pixel_count as f64,
+            }
+        })
+        .collect();
+    palette.sort_by(|a, b| b.weight.total_cmp(&a.weight));
+
+    palette
+}
+
+/// Picks the most prominent colourful entry from a palette, falling back to the most dominant
+/// colour when the palette is entirely greys.
+pub fn accent_color(palette: &[PaletteColor]) -> Option<Rgb<u8>> {
+    let score = |entry: &PaletteColor| entry.weight.sqrt() * chroma(entry.color);
+
+    palette
+        .iter()
+        .filter(|entry| chroma(entry.color) > 0.0)
+        .max_by(|a, b| score(a).total_cmp(&score(b)))
+        .or_else(|| palette.first())
+        .map(|entry| entry.color)
+}
+
+/// The channel with the largest spread of values, along with that spread.
+fn widest_channel(pixels: &[[u8; 3]]) -> (usize, u8) {
+    (0..3)
+        .map(|channel| {
+            let (min, max) = pixels.iter().fold((u8::MAX, u8::MIN), |(min, max), pixel| {
+                (min.min(pixel[channel]), max.max(pixel[channel]))
+            });
+
+            (channel, max - min)
+        })
+        .max_by_key(|(_, range)| *range)
+        .expect("there should be three channels")
+}
+
+/// How colourful a colour is, from 0 for greys to 1 for fully saturated colours.
+fn chroma(color: Rgb<u8>) -> f64 {
+    let max = color.0.into_iter().max().unwrap_or_default();
+    let min = color.0.into_iter().min().unwrap_or_default();
+
+    (max - min) as f64 / 255.0
+}
@@ -0,0 +1,100 @@
+use std::ops::Range;
+
+/// How a run of text is styled.
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Style {
+    pub bold: bool,
+    pub italic: bool,
+    pub underline: bool,
+    pub strikethrough: bool,
+    pub code: bool,
+}
+
+/// A run of text in a single style.
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct Span {
+    pub range: Range<usize>,
+    pub style: Style,
+}
+
+/// Text made up of differently styled runs.
+#[derive(Default, Debug, Clone, PartialEq, Eq, Hash)]
+pub struct RichText {
+    text: String,
+    /// Runs covering the whole text in order, with neighbouring runs always differing in style.
+    spans: Vec<Span>,
+}
+
+impl RichText {
+    pub fn plain(text: &str) -> Self {
+        let mut rich_text = Self::default();
+        rich_text.push(text, Style::default());
+
+        rich_text
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    pub fn len(&self) -> usize {
+        self.text.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.text.is_empty()
+    }
+
+    pub fn spans(&self) -> &[Span] {
+        &self.spans
+    }
+
+    /// Appends text in the given style.
+    pub fn push(&mut self, text: &str, style: Style) {
+        if text.is_empty() {
+            return;
+        }
+
+        let start = self.text.len();
+        self.text.push_str(text);
+
+        match self.spans.last_mut() {
+            Some(span) if span.style == style => span.range.end = self.text.len(),
+            _ => self.spans.push(Span {
+                range: start..self.text.len(),
+                style,
+            }),
+        }
+    }
+
+    /// Appends text in the same style as the end of the existing text.
+    pub fn push_str(&mut self, text: &str) {
+        let style = self.spans.last().map(|span| span.style).unwrap_or_default();
+
+        self.push(text, style);
+    }
+
+    /// Copies out part of the text, keeping its styles.
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        let mut slice = Self::default();
+        for span in &self.spans {
+            let start = span.range.start.max(range.start);
+            let end = span.range.end.min(range.end);
+            if start < end {
+                slice.push(&self.text[start..end], span.style);
+            }
+        }
+
+        slice
+    }
+
+    /// Shortens the text to `len` bytes.
+    pub fn truncate(&mut self, len: usize) {
+        self.text.truncate(len);
+
+        self.spans.retain(|span| span.range.start < len);
+        if let Some(span) = self.spans.last_mut() {
+            span.range.end = span.range.end.min(len);
+        }
+    }
+}
@@ -0,0 +1,439 @@
+use image::{imageops, GrayImage, ImageBuffer, Luma, Pixel, Rgba, RgbaImage};
+use imageproc::distance_transform::euclidean_squared_distance_transform;
+
+use super::BoxStyle;
+
+/// How far a gaussian blur spreads, in multiples of its standard deviation.
+const BLUR_EXTENT: f64 = 3.0;
+/// The widest blur done at full resolution. Wider ones are done on a shrunk copy, which is much
+/// quicker and looks the same.
+const MAX_FULL_RESOLUTION_BLUR: f32 = 4.0;
+
+/// A padded box laid out on a card, with everything about it that doesn't depend on what's behind
+/// it worked out ahead of time, so it can be drawn over many frames cheaply.
+pub(super) struct TextBox {
+    style: BoxStyle,
+    position: (u32, u32),
+    /// How much of each pixel of the box it covers.
+    coverage: GrayImage,
+    /// How much of each pixel of the box its border covers, if it has one.
+    border_coverage: Option<GrayImage>,
+    shadow: Option<Mask>,
+}
+
+impl TextBox {
+    /// Lays out a box holding content of `content_dimensions`, positioned within the area of
+    /// `max_dimensions` at `max_position`.
+    pub(super) fn new(
+        content_dimensions: (u32, u32),
+        style: &BoxStyle,
+        max_position: (u32, u32),
+        max_dimensions: (u32, u32),
+    ) -> Self {
+        let padding_size = style.padding_size;
+
+        let dimensions = (
+            content_dimensions.0 + padding_size * 2,
+            content_dimensions.1 + padding_size * 2,
+        );
+        let offset = style.alignment.position(dimensions, max_dimensions);
+        let position = (max_position.0 + offset.0, max_position.1 + offset.1);
+
+        let shape = RoundedRect::new(position, dimensions, style.corner_radius);
+        let coverage_mask = |shape: &RoundedRect| {
+            GrayImage::from_fn(dimensions.0, dimensions.1, |x, y| {
+                let coverage = shape.coverage((position.0 + x) as i64, (position.1 + y) as i64);
+
+                Luma([(coverage * 255.0).round() as u8])
+            })
+        };
+
+        let coverage = coverage_mask(&shape);
+        let border_coverage = (style.border_width > 0).then(|| {
+            let mut border_coverage = coverage.clone();
+            let inner_coverage = coverage_mask(&shape.inset(style.border_width as f64));
+            for (pixel, Luma([inner])) in border_coverage.pixels_mut().zip(inner_coverage.pixels())
+            {
+                pixel.0[0] = pixel.0[0].saturating_sub(*inner);
+            }
+
+            border_coverage
+        });
+
+        let shadow = (style.shadow_color.0[3] > 0).then(|| shadow_mask(&shape, style));
+
+        Self {
+            style: *style,
+            position,
+            coverage,
+            border_coverage,
+            shadow,
+        }
+    }
+
+    /// Prepares `image` to be drawn inside the box, along with the outline and shadow around it
+    /// that the box's style asks for. It should be the size the box was laid out for.
+    pub(super) fn content(&self, image: RgbaImage) -> BoxContent {
+        let (outline, shadow) = text_effects(&image, &self.style);
+
+        BoxContent {
+            image,
+            outline,
+            shadow,
+        }
+    }
+
+    /// A blurred copy of what's behind the box in `image`, for a frosted-glass look, if its style
+    /// asks for one. It only needs taking again if what's behind the box changes.
+    pub(super) fn backdrop(&self, image: &RgbaImage) -> Option<RgbaImage> {
+        (self.style.backdrop_blur > 0).then(|| {
+            blurred_region(
+                image,
+                self.position,
+                self.coverage.dimensions(),
+                self.style.backdrop_blur,
+            )
+        })
+    }
+
+    /// Draws the box onto `image` holding `content`, over its `backdrop`, with everything drawn at
+    /// `opacity`.
+    pub(super) fn draw(
+        &self,
+        image: &mut RgbaImage,
+        content: &BoxContent,
+        backdrop: Option<&RgbaImage>,
+        opacity: f32,
+    ) {
+        let opacity = opacity.clamp(0.0, 1.0) as f64;
+        if opacity <= 0.0 {
+            return;
+        }
+
+        if let Some(shadow) = &self.shadow {
+            shadow.draw(image, (0, 0), self.style.shadow_color, opacity);
+        }
+
+        for (x, y, Luma([coverage])) in self.coverage.enumerate_pixels() {
+            if *coverage == 0 {
+                continue;
+            }
+            let coverage = *coverage as f64 / 255.0;
+
+            let pixel = image.get_pixel_mut(self.position.0 + x, self.position.1 + y);
+            if let Some(backdrop) = backdrop {
+                blend(pixel, *backdrop.get_pixel(x, y), coverage * opacity);
+            }
+            blend(pixel, self.style.box_color, coverage * opacity);
+
+            if let Some(border_coverage) = &self.border_coverage {
+                let border_coverage = border_coverage.get_pixel(x, y).0[0] as f64 / 255.0;
+                blend(pixel, self.style.border_color, border_coverage * opacity);
+            }
+        }
+
+        let content_position = (
+            self.position.0 + self.style.padding_size,
+            self.position.1 + self.style.padding_size,
+        );
+        let content_offset = (content_position.0 as i64, content_position.1 as i64);
+
+        if let Some(text_shadow) = &content.shadow {
+            text_shadow.draw(image, content_offset, self.style.text_shadow_color, opacity);
+        }
+        if let Some(outline) = &content.outline {
+            outline.draw(
+                image,
+                content_offset,
+                self.style.text_outline_color,
+                opacity,
+            );
+        }
+
+        if opacity >= 1.0 {
+            imageops::overlay(image, &content.image, content_offset.0, content_offset.1);
+        } else {
+            let mut faded_content = content.image.clone();
+            fade(&mut faded_content, opacity as f32);
+            imageops::overlay(image, &faded_content, content_offset.0, content_offset.1);
+        }
+    }
+}
+
+/// What's drawn inside a box, along with the outline and shadow around it.
+pub(super) struct BoxContent {
+    image: RgbaImage,
+    outline: Option<Mask>,
+    shadow: Option<Mask>,
+}
+
+/// A greyscale image where each pixel is how much of a colour is drawn, at a position relative to
+/// whatever it belongs to.
+struct Mask {
+    image: GrayImage,
+    position: (i64, i64),
+}
+
+impl Mask {
+    fn draw(&self, image: &mut RgbaImage, offset: (i64, i64), color: Rgba<u8>, opacity: f64) {
+        draw_mask(
+            image,
+            &self.image,
+            (self.position.0 + offset.0, self.position.1 + offset.1),
+            color,
+            opacity,
+        );
+    }
+}
+
+/// Scales the opacity of every pixel in `image` by `opacity`.
+fn fade(image: &mut RgbaImage, opacity: f32) {
+    if opacity >= 1.0 {
+        return;
+    }
+
+    for pixel in image.pixels_mut() {
+        pixel.0[3] = (pixel.0[3] as f32 * opacity.max(0.0)).round() as u8;
+    }
+}
+
+/// The soft shadow cast by a box of `shape`, leaving the area under the box itself clear so it
+/// doesn't show through a translucent box.
+fn shadow_mask(shape: &RoundedRect, style: &BoxStyle) -> Mask {
+    let margin = (style.shadow_blur as f64 * BLUR_EXTENT).ceil() as u32;
+    let box_dimensions = (shape.dimensions.0 as u32, shape.dimensions.1 as u32);
+
+    let mask_shape = RoundedRect::new((margin, margin), box_dimensions, style.corner_radius);
+    let mut mask = GrayImage::from_fn(
+        box_dimensions.0 + margin * 2,
+        box_dimensions.1 + margin * 2,
+        |x, y| Luma([(mask_shape.coverage(x as i64, y as i64) * 255.0).round() as u8]),
+    );
+    if style.shadow_blur > 0 {
+        mask = blur(&mask, style.shadow_blur);
+    }
+
+    let position = (
+        shape.position.0 as i64 - margin as i64,
+        shape.position.1 as i64 - margin as i64 + style.shadow_offset as i64,
+    );
+    for (x, y, Luma([value])) in mask.enumerate_pixels_mut() {
+        let box_coverage = shape.coverage(position.0 + x as i64, position.1 + y as i64);
+        *value = (*value as f64 * (1.0 - box_coverage)).round() as u8;
+    }
+
+    Mask {
+        image: mask,
+        position,
+    }
+}
+
+/// The outline and shadow around `content`, as its style asks, from how much of each pixel its
+/// glyphs cover. Both are positioned relative to the content.
+fn text_effects(content: &RgbaImage, style: &BoxStyle) -> (Option<Mask>, Option<Mask>) {
+    let outline_width = if style.text_outline_color.0[3] > 0 {
+        style.text_outline_width
+    } else {
+        0
+    };
+    let has_shadow = style.text_shadow_color.0[3] > 0;
+    if outline_width == 0 && !has_shadow {
+        return (None, None);
+    }
+
+    // the content's only drawn in one colour, besides its emoji and avatar, so its opacity is how
+    // much of each pixel it covers
+    let margin = outline_width + (style.text_shadow_blur as f64 * BLUR_EXTENT).ceil() as u32;
+    let mut mask = GrayImage::new(content.width() + margin * 2, content.height() + margin * 2);
+    for (x, y, pixel) in content.enumerate_pixels() {
+        mask.put_pixel(x + margin, y + margin, Luma([pixel.0[3]]));
+    }
+    if outline_width > 0 {
+        mask = dilate(&mask, outline_width);
+    }
+
+    let position = (-(margin as i64), -(margin as i64));
+
+    let shadow = has_shadow.then(|| {
+        let shadow_mask = if style.text_shadow_blur > 0 {
+            blur(&mask, style.text_shadow_blur)
+        } else {
+            mask.clone()
+        };
+
+        Mask {
+            image: shadow_mask,
+            position: (position.0, position.1 + style.text_shadow_offset as i64),
+        }
+    });
+    let outline = (outline_width > 0).then_some(Mask {
+        image: mask,
+        position,
+    });
+
+    (outline, shadow)
+}
+
+/// Grows the shapes in `mask` by `distance` pixels in every direction, keeping their edges smooth.
+fn dilate(mask: &GrayImage, distance: u32) -> GrayImage {
+    // pixels at least half covered count as inside a shape
+    let inside = GrayImage::from_fn(mask.width(), mask.height(), |x, y| {
+        Luma([if mask.get_pixel(x, y).0[0] >= 128 {
+            255
+        } else {
+            0
+        }])
+    });
+    let squared_distances = euclidean_squared_distance_transform(&inside);
+
+    GrayImage::from_fn(mask.width(), mask.height(), |x, y| {
+        let Luma([squared_distance]) = *squared_distances.get_pixel(x, y);
+        let coverage = (distance as f64 + 0.5 - squared_distance.sqrt()).clamp(0.0, 1.0);
+
+        Luma([mask.get_pixel(x, y).0[0].max((coverage * 255.0).round() as u8)])
+    })
+}
+
+/// Draws `color` onto `image` with `mask` at `position`, where each pixel of the mask is how much
+/// of the colour is drawn.
+fn draw_mask(
+    image: &mut RgbaImage,
+    mask: &GrayImage,
+    position: (i64, i64),
+    color: Rgba<u8>,
+    opacity: f64,
+) {
+    for (mask_x, mask_y, Luma([value])) in mask.enumerate_pixels() {
+        let (Ok(x), Ok(y)) = (
+            u32::try_from(position.0 + mask_x as i64),
+            u32::try_from(position.1 + mask_y as i64),
+        ) else {
+            continue;
+        };
+        if x >= image.width() || y >= image.height() {
+            continue;
+        }
+
+        blend(
+            image.get_pixel_mut(x, y),
+            color,
+            *value as f64 / 255.0 * opacity,
+        );
+    }
+}
+
+/// A blurred copy of the part of `image` at `position` of `dimensions`, sampling the image around
+/// it too so its edges aren't left unblurred.
+fn blurred_region(
+    image: &RgbaImage,
+    position: (u32, u32),
+    dimensions: (u32, u32),
+    blur: u32,
+) -> RgbaImage {
+    let margin = (blur as f64 * BLUR_EXTENT).ceil() as u32;
+
+    let region_start = (
+        position.0.saturating_sub(margin),
+        position.1.saturating_sub(margin),
+    );
+    let region_end = (
+        (position.0 + dimensions.0 + margin).min(image.width()),
+        (position.1 + dimensions.1 + margin).min(image.height()),
+    );
+    let region = imageops::crop_imm(
+        image,
+        region_start.0,
+        region_start.1,
+        region_end.0 - region_start.0,
+        region_end.1 - region_start.1,
+    );
+
+    let blurred = self::blur(&region.to_image(), blur);
+
+    imageops::crop_imm(
+        &blurred,
+        position.0 - region_start.0,
+        position.1 - region_start.1,
+        dimensions.0,
+        dimensions.1,
+    )
+    .to_image()
+}
+
+/// Blurs `image` with a gaussian blur whose standard deviation is `sigma` pixels.
+fn blur<P>(image: &ImageBuffer<P, Vec<u8>>, sigma: u32) -> ImageBuffer<P, Vec<u8>>
+where
+    P: Pixel<Subpixel = u8> + 'static,
+{
+    let scale = (sigma as f32 / MAX_FULL_RESOLUTION_BLUR).max(1.0);
+    if scale == 1.0 {
+        return imageops::blur(image, sigma as f32);
+    }
+
+    let (width, height) = image.dimensions();
+    let shrunk = imageops::resize(
+        image,
+        ((width as f32 / scale).ceil() as u32).max(1),
+        ((height as f32 / scale).ceil() as u32).max(1),
+        imageops::FilterType::Triangle,
+    );
+    let blurred = imageops::blur(&shrunk, sigma as f32 / scale);
+
+    imageops::resize(&blurred, width, height, imageops::FilterType::Triangle)
+}
+
+/// Blends `color` over `pixel`, with its opacity scaled by `coverage`.
+fn blend(pixel: &mut Rgba<u8>, mut color: Rgba<u8>, coverage: f64) {
+    if coverage <= 0.0 {
+        return;
+    }
+
+    color.0[3] = (color.0[3] as f64 * coverage.min(1.0)).round() as u8;
+    pixel.blend(&color);
+}
+
+/// A rectangle with rounded corners, for working out how much of each pixel a box covers.
+struct RoundedRect {
+    position: (f64, f64),
+    dimensions: (f64, f64),
+    radius: f64,
+}
+
+impl RoundedRect {
+    fn new(position: (u32, u32), dimensions: (u32, u32), radius: u32) -> Self {
+        let radius = radius.min(dimensions.0 / 2).min(dimensions.1 / 2);
+
+        Self {
+            position: (position.0 as f64, position.1 as f64),
+            dimensions: (dimensions.0 as f64, dimensions.1 as f64),
+            radius: radius as f64,
+        }
+    }
+
+    /// The same shape shrunk by `distance` on every side, with its corners rounded to match.
+    fn inset(&self, distance: f64) -> Self {
+        Self {
+            position: (self.position.0 + distance, self.position.1 + distance),
+            dimensions: (
+                (self.dimensions.0 - distance * 2.0).max(0.0),
+                (self.dimensions.1 - distance * 2.0).max(0.0),
+            ),
+            radius: (self.radius - distance).max(0.0),
+        }
+    }
+
+    /// How much of the pixel at `x`, `y` the shape covers, from 0 to 1, approximated by how far
+    /// its centre is inside the shape.
+    fn coverage(&self, x: i64, y: i64) -> f64 {
+        let half_dimensions = (self.dimensions.0 / 2.0, self.dimensions.1 / 2.0);
+
+        // the distance from the centre of the pixel to the shape's edge, negative inside it
+        let dx = (x as f64 + 0.5 - self.position.0 - half_dimensions.0).abs() - half_dimensions.0
+            + self.radius;
+        let dy = (y as f64 + 0.5 - self.position.1 - half_dimensions.1).abs() - half_dimensions.1
+            + self.radius;
+        let distance = dx.max(0.0).hypot(dy.max(0.0)) + dx.max(dy).min(0.0) - self.radius;
+
+        (0.5 - distance).clamp(0.0, 1.0)
+    }
+}
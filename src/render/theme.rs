@@ -0,0 +1,264 @@
+use std::fmt;
+
+use chrono::format::{Item, StrftimeItems};
+use image::{Pixel, Rgb, Rgba};
+use serde::{Deserialize, Serialize};
+
+use super::{color, shorter_side, Filter};
+
+// padding has to leave room inside the smallest box any layout draws, which is the classic
+// layout's on a card no taller than it is wide: its quote box is 0.45 of the card's shorter side
+// high, and its attribution box 0.6 / 7
+const MAX_QUOTE_PADDING: f64 = 0.2;
+const MAX_ATTRIBUTION_PADDING: f64 = 0.04;
+
+/// Colours, opacity and spacing of a card, as loaded from a theme file.
+///
+/// Lengths are fractions of the card's shorter side so themes look the same at any output size
+/// and shape.
+#[derive(Debug, Serialize, Deserialize, Clone, PartialEq)]
+#[serde(default, deny_unknown_fields)]
+pub struct Theme {
+    #[serde(with = "hex_color")]
+    pub box_color: Rgb<u8>,
+    pub box_opacity: f64,
+    /// How far the box colour is blended towards the background's accent colour, from 0 to 1.
+    pub box_tint: f64,
+    pub text_color: TextColor,
+    pub corner_radius: f64,
+    /// How much the photo behind each box is blurred, for a frosted-glass look.
+    pub backdrop_blur: f64,
+    pub border_width: f64,
+    #[serde(with = "hex_color")]
+    pub border_color: Rgb<u8>,
+    pub border_opacity: f64,
+    /// How dark the soft shadow under each box is, from 0 for none to 1.
+    pub shadow_opacity: f64,
+    pub shadow_blur: f64,
+    /// How far below each box its shadow falls.
+    pub shadow_offset: f64,
+    /// How thick the outline drawn around the text is, where 0 draws none.
+    pub text_outline_width: f64,
+    #[serde(with = "hex_color")]
+    pub text_outline_color: Rgb<u8>,
+    /// How dark the shadow behind the text is, from 0 for none to 1.
+    pub text_shadow_opacity: f64,
+    pub text_shadow_blur: f64,
+    /// How far below the text its shadow falls.
+    pub text_shadow_offset: f64,
+    pub quote_padding: f64,
+    pub attribution_padding: f64,
+    pub date_format: String,
+    /// Whether the quote's lines are evened out so the last isn't left with a lone word.
+    pub balance_lines: bool,
+    /// The smallest the quote is drawn before it's cut short instead.
+    pub min_font_size: f64,
+    /// Applied to the background photo, in order, before anything is drawn on it.
+    pub filters: Vec<Filter>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self {
+            box_color: Rgb([255, 255, 255]),
+            box_opacity: 0.6,
+            box_tint: 0.0,
+            text_color: TextColor::Accent,
+            corner_radius: 0.0,
+            backdrop_blur: 0.0,
+            border_width: 0.0,
+            border_color: Rgb([255, 255, 255]),
+            border_opacity: 0.5,
+            shadow_opacity: 0.0,
+            shadow_blur: 0.02,
+            shadow_offset: 0.01,
+            text_outline_width: 0.0,
+            text_outline_color: Rgb([0, 0, 0]),
+            text_shadow_opacity: 0.0,
+            text_shadow_blur: 0.005,
+            text_shadow_offset: 0.003,
+            quote_padding: 0.05,
+            attribution_padding: 0.025,
+            date_format: String::from("%d/%m/%Y"),
+            balance_lines: true,
+            min_font_size: 0.03,
+            filters: Vec::new(),
+        }
+    }
+}
+
+impl Theme {
+    pub fn from_toml(s: &str) -> Result<Self, ThemeError> {
+        let theme: Theme = toml::from_str(s).map_err(ThemeError::Parse)?;
+        theme.validate()?;
+
+        Ok(theme)
+    }
+
+    fn validate(&self) -> Result<(), ThemeError> {
+        if !(0.0..=1.0).contains(&self.box_opacity) {
+            return Err(ThemeError::Invalid("`box_opacity` must be between 0 and 1"));
+        }
+        if !(0.0..=1.0).contains(&self.box_tint) {
+            return Err(ThemeError::Invalid("`box_tint` must be between 0 and 1"));
+        }
+        if !(0.0..=1.0).contains(&self.border_opacity) {
+            return Err(ThemeError::Invalid(
+                "`border_opacity` must be between 0 and 1",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.shadow_opacity) {
+            return Err(ThemeError::Invalid(
+                "`shadow_opacity` must be between 0 and 1",
+            ));
+        }
+        if !(0.0..=1.0).contains(&self.text_shadow_opacity) {
+            return Err(ThemeError::Invalid(
+                "`text_shadow_opacity` must be between 0 and 1",
+            ));
+        }
+
+        let lengths = [self.corner_radius, self.border_width, self.shadow_offset];
+        if lengths.iter().any(|length| !(0.0..=0.5).contains(length)) {
+            return Err(ThemeError::Invalid(
+                "`corner_radius`, `border_width` and `shadow_offset` must be between 0 and 0.5",
+            ));
+        }
+
+        if !(0.0..=MAX_QUOTE_PADDING).contains(&self.quote_padding) {
+            return Err(ThemeError::Invalid(
+                "`quote_padding` must be between 0 and 0.2",
+            ));
+        }
+        if !(0.0..=MAX_ATTRIBUTION_PADDING).contains(&self.attribution_padding) {
+            return Err(ThemeError::Invalid(
+                "`attribution_padding` must be between 0 and 0.04",
+            ));
+        }
+
+        let effect_sizes = [
+            self.backdrop_blur,
+            self.shadow_blur,
+            self.text_outline_width,
+            self.text_shadow_blur,
+            self.text_shadow_offset,
+        ];
+        if effect_sizes.iter().any(|size| !(0.0..=0.1).contains(size)) {
+            return Err(ThemeError::Invalid(
+                "`backdrop_blur`, `shadow_blur`, `text_outline_width`, `text_shadow_blur` and `text_shadow_offset` must be between 0 and 0.1",
+            ));
+        }
+
+        if !(self.min_font_size > 0.0 && self.min_font_size <= 0.5) {
+            return Err(ThemeError::Invalid(
+                "`min_font_size` must be above 0 and at most 0.5",
+            ));
+        }
+
+        for filter in &self.filters {
+            filter.validate().map_err(ThemeError::Invalid)?;
+        }
+
+        if StrftimeItems::new(&self.date_format).any(|item| matches!(item, Item::Error)) {
+            return Err(ThemeError::Invalid(
+                "`date_format` is not a valid format string",
+            ));
+        }
+
+        Ok(())
+    }
+
+    pub(crate) fn box_rgba(&self, accent_color: Rgb<u8>) -> Rgba<u8> {
+        color::with_opacity(
+            color::mix(self.box_color, accent_color, self.box_tint),
+            self.box_opacity,
+        )
+    }
+
+    pub(crate) fn border_rgba(&self) -> Rgba<u8> {
+        color::with_opacity(self.border_color, self.border_opacity)
+    }
+
+    pub(crate) fn shadow_rgba(&self) -> Rgba<u8> {
+        color::with_opacity(Rgb([0, 0, 0]), self.shadow_opacity)
+    }
+
+    pub(crate) fn text_outline_rgba(&self) -> Rgba<u8> {
+        self.text_outline_color.to_rgba()
+    }
+
+    pub(crate) fn text_shadow_rgba(&self) -> Rgba<u8> {
+        color::with_opacity(Rgb([0, 0, 0]), self.text_shadow_opacity)
+    }
+
+    pub(crate) fn length(&self, fraction: f64, card_dimensions: (u32, u32)) -> u32 {
+        (shorter_side(card_dimensions) as f64 * fraction) as u32
+    }
+}
+
+/// How the colour of the text is chosen. Either way, it's adjusted where needed to keep the text
+/// legible against its box.
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
+#[serde(rename_all = "lowercase")]
+pub enum TextColor {
+    /// The average colour of the background image.
+    Average,
+    /// The most prominent colourful entry in the background image's palette.
+    Accent,
+    /// A fixed colour.
+    Fixed(#[serde(with = "hex_color")] Rgb<u8>),
+}
+
+#[derive(Debug)]
+pub enum ThemeError {
+    Parse(toml::de::Error),
+    Invalid(&'static str),
+}
+
+impl fmt::Display for ThemeError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ThemeError::Parse(err) => write!(f, "failed to parse theme: {err}"),
+            ThemeError::Invalid(reason) => write!(f, "invalid theme: {reason}"),
+        }
+    }
+}
+
+impl std::error::Error for ThemeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            ThemeError::Parse(err) => Some(err),
+            ThemeError::Invalid(_) => None,
+        }
+    }
+}
+
+/// (De)serializes colours as `#RRGGBB` strings.
+pub(crate) mod hex_color {
+    use image::Rgb;
+    use serde::{de, Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(color: &Rgb<u8>, serializer: S) -> Result<S::Ok, S::Error> {
+        let Rgb([r, g, b]) = color;
+
+        serializer.serialize_str(&format!("#{r:02X}{g:02X}{b:02X}"))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<Rgb<u8>, D::Error> {
+        let s = String::deserialize(deserializer)?;
+
+        parse(&s)
+            .ok_or_else(|| de::Error::custom(format!("expected a `#RRGGBB` colour, got `{s}`")))
+    }
+
+    /// Parses a `#RRGGBB` colour.
+    pub fn parse(s: &str) -> Option<Rgb<u8>> {
+        let hex = s
+            .strip_prefix('#')
+            .filter(|hex| hex.len() == 6 && hex.is_ascii())?;
+
+        let channel = |index: usize| u8::from_str_radix(&hex[index..index + 2], 16).ok();
+
+        Some(Rgb([channel(0)?, channel(2)?, channel(4)?]))
+    }
+}
@@ -0,0 +1,404 @@
+use std::ops::Range;
+
+use image::{imageops, Rgba, RgbaImage};
+use imageproc::{drawing, pixelops, rect::Rect};
+use rusttype::{point, GlyphId, PositionedGlyph, Scale};
+use rustybuzz::UnicodeBuffer;
+use unicode_bidi::Level;
+
+use super::{
+    bidi::{self, Direction},
+    rich_text::{RichText, Style},
+};
+use crate::{
+    assets::{
+        emoji::Emoji,
+        fonts::{FontChain, FontFamily},
+    },
+    custom_emoji::{CustomEmoji, CustomEmojiImages},
+};
+
+const EMOJI_SIZE_MULTIPLIER: f32 = 0.9;
+// how far emoji sit below the baseline, as a fraction of their size
+const EMOJI_DESCENT_MULTIPLIER: f32 = 0.1;
+const EMOJI_GAP_MULTIPLIER: f32 = 0.08;
+
+/// Lays out and draws single lines of styled text, drawing each character with the first font in
+/// its style's chain that has it, and emoji from images where no font does. Lines are reordered
+/// for display following the Unicode bidirectional algorithm, and shaped so kerning, ligatures
+/// and combining marks come out right.
+///
+/// Stands in for `imageproc`'s text functions, which draw everything from one font and place
+/// glyphs one at a time.
+pub struct Typesetter<'a> {
+    pub fonts: FontFamily,
+    pub custom_emoji: &'a CustomEmojiImages,
+    /// The direction of the paragraph the lines come from.
+    pub direction: Direction,
+}
+
+enum Segment<'a> {
+    /// A run of text in one style, font and direction.
+    Text {
+        style: Style,
+        font_index: usize,
+        text: &'a str,
+        rtl: bool,
+    },
+    Emoji(Emoji),
+    CustomEmoji(&'a RgbaImage),
+}
+
+impl Typesetter<'_> {
+    /// Gets the width and height of some text as it would be drawn by
+    /// [`Typesetter::draw_text_mut`], in the same way as [`imageproc::drawing::text_size`].
+    pub fn text_size(&self, scale: Scale, text: &RichText) -> (i32, i32) {
+        self.layout(scale, text, |_, _, _| {})
+    }
+
+    pub fn draw_text_mut(
+        &self,
+        image: &mut RgbaImage,
+        color: Rgba<u8>,
+        x: i32,
+        y: i32,
+        scale: Scale,
+        text: &RichText,
+    ) {
+        let emoji_top = self.emoji_top(scale);
+
+        self.layout(scale, text, |segment, offset, width| {
+            let resize = |emoji_image: &RgbaImage| {
+                imageops::resize(
+                    emoji_image,
+                    width,
+                    emoji_size(scale),
+                    imageops::FilterType::Triangle,
+                )
+            };
+
+            let emoji_image = match segment {
+                Segment::Text {
+                    style,
+                    font_index,
+                    text,
+                    rtl,
+                } => {
+                    let (glyphs, _) = self.shape(scale, *style, *font_index, text, *rtl);
+                    draw_glyphs(image, color, x + offset, y, &glyphs);
+                    self.draw_decorations(image, color, x + offset, y, scale, *style, width);
+                    return;
+                }
+                Segment::Emoji(emoji) => resize(&emoji.image()),
+                Segment::CustomEmoji(emoji_image) => resize(emoji_image),
+            };
+
+            imageops::overlay(
+                image,
+                &emoji_image,
+                (x + offset) as i64,
+                (y + emoji_top) as i64,
+            );
+        });
+    }
+
+    /// Lays out each segment of `text` along a line, calling `f` with each one, its horizontal
+    /// offset and its width, and returning the overall width and height.
+    fn layout(
+        &self,
+        scale: Scale,
+        text: &RichText,
+        mut f: impl FnMut(&Segment, i32, u32),
+    ) -> (i32, i32) {
+        let (mut width, mut height) = (0, 0);
+
+        let mut offset = 0.0;
+        for segment in self.segments(text) {
+            let image_width = match &segment {
+                Segment::Text { .. } => 0,
+                Segment::Emoji(_) => emoji_size(scale),
+                Segment::CustomEmoji(image) => {
+                    let size = emoji_size(scale);
+
+                    // wide custom emoji keep their aspect ratio, within reason
+                    let aspect_ratio = (image.width() as f32 / image.height() as f32).min(3.0);
+
+                    (size as f32 * aspect_ratio) as u32
+                }
+            };
+
+            let (segment_width, segment_height, advance) = match &segment {
+                Segment::Text {
+                    style,
+                    font_index,
+                    text,
+                    rtl,
+                } => {
+                    let (glyphs, advance) = self.shape(scale, *style, *font_index, text, *rtl);
+                    let (segment_width, segment_height) = glyphs
+                        .iter()
+                        .filter_map(|glyph| glyph.pixel_bounding_box())
+                        .fold((0, 0), |(width, height), bounding_box| {
+                            (
+                                width.max(bounding_box.max.x),
+                                height.max(bounding_box.max.y),
+                            )
+                        });
+
+                    (segment_width, segment_height, advance)
+                }
+                Segment::Emoji(_) | Segment::CustomEmoji(_) => (
+                    image_width as i32,
+                    self.emoji_top(scale) + emoji_size(scale) as i32,
+                    image_width as f32 + emoji_size(scale) as f32 * EMOJI_GAP_MULTIPLIER,
+                ),
+            };
+
+            let drawn_width = match &segment {
+                Segment::Text { .. } => advance.round() as u32,
+                Segment::Emoji(_) | Segment::CustomEmoji(_) => image_width,
+            };
+            f(&segment, offset as i32, drawn_width);
+
+            width = width.max(offset as i32 + segment_width);
+            height = height.max(segment_height);
+            offset += advance;
+        }
+
+        (width, height)
+    }
+
+    /// Splits text into runs of each style and font, emoji no font can draw, and custom emoji, in
+    /// the order they're displayed from left to right.
+    fn segments<'b>(&'b self, rich_text: &'b RichText) -> Vec<Segment<'b>> {
+        let text = rich_text.as_str();
+        let levels = bidi::levels(text, self.direction);
+        let mut segments = Vec::new();
+
+        let mut text_start = 0;
+        let mut index = 0;
+        while let Some(rest) = text.get(index..).filter(|rest| !rest.is_empty()) {
+            let first_char = rest.chars().next().expect("rest should not be empty");
+
+            // lone characters with both text and emoji forms are left to the fonts where possible
+            let emoji = Emoji::match_start(rest)
+                .filter(|emoji| emoji.explicit || !self.fonts.plain.covers(first_char));
+
+            let (segment, len) = if let Some(emoji) = emoji {
+                let len = emoji.len;
+
+                (Segment::Emoji(emoji), len)
+            } else if let Some(custom_emoji) = CustomEmoji::match_start(rest) {
+                let segment = match self.custom_emoji.get(&custom_emoji.id) {
+                    Some(image) => Segment::CustomEmoji(image.as_ref()),
+                    None => Segment::Text {
+                        style: Style::default(),
+                        font_index: 0,
+                        text: custom_emoji.shortcode,
+                        rtl: false,
+                    },
+                };
+
+                (segment, custom_emoji.len)
+            } else {
+                index += first_char.len_utf8();
+                continue;
+            };
+
+            self.push_text_segments(&mut segments, rich_text, &levels, text_start..index);
+            segments.push((levels[index], segment));
+
+            index += len;
+            text_start = index;
+        }
+
+        self.push_text_segments(&mut segments, rich_text, &levels, text_start..text.len());
+
+        bidi::reorder(segments)
+    }
+
+    /// Splits the text in `range` wherever its style, font or direction changes.
+    fn push_text_segments<'b>(
+        &self,
+        segments: &mut Vec<(Level, Segment<'b>)>,
+        rich_text: &'b RichText,
+        levels: &[Level],
+        range: Range<usize>,
+    ) {
+        let text = rich_text.as_str();
+
+        for span in rich_text.spans() {
+            let span_start = span.range.start.max(range.start);
+            let span_end = span.range.end.min(range.end);
+            if span_start >= span_end {
+                continue;
+            }
+
+            let mut run_start = span_start;
+            for (font_index, font_run) in self.fonts(span.style).runs(&text[span_start..span_end]) {
+                let font_run_end = run_start + font_run.len();
+
+                while run_start < font_run_end {
+                    let level = levels[run_start];
+                    let run_end = text[run_start..font_run_end]
+                        .char_indices()
+                        .map(|(index, _)| run_start + index)
+                        .find(|index| levels[*index] != level)
+                        .unwrap_or(font_run_end);
+
+                    let segment = Segment::Text {
+                        style: span.style,
+                        font_index,
+                        text: &text[run_start..run_end],
+                        rtl: level.is_rtl(),
+                    };
+
+                    segments.push((level, segment));
+                    run_start = run_end;
+                }
+            }
+        }
+    }
+
+    fn fonts(&self, style: Style) -> &FontChain {
+        match style {
+            Style { code: true, .. } => &self.fonts.code,
+            Style {
+                bold: true,
+                italic: true,
+                ..
+            } => &self.fonts.bold_italic,
+            Style { bold: true, .. } => &self.fonts.bold,
+            Style { italic: true, .. } => &self.fonts.italic,
+            _ => &self.fonts.plain,
+        }
+    }
+
+    /// Shapes a run of text in one font, returning its glyphs in display order, positioned
+    /// relative to the top left of the line, along with how far the run advances.
+    ///
+    /// Every font shares the plain text's primary font's baseline.
+    fn shape(
+        &self,
+        scale: Scale,
+        style: Style,
+        font_index: usize,
+        text: &str,
+        rtl: bool,
+    ) -> (Vec<PositionedGlyph<'static>>, f32) {
+        let font = self.fonts(style).get(font_index);
+
+        let mut buffer = UnicodeBuffer::new();
+        buffer.push_str(text);
+        buffer.set_direction(if rtl {
+            rustybuzz::Direction::RightToLeft
+        } else {
+            rustybuzz::Direction::LeftToRight
+        });
+        let glyph_buffer = rustybuzz::shape(&font.face, &[], buffer);
+
+        // rusttype scales fonts by the height from their descent to their ascent, not the em
+        let v_metrics = font.font.v_metrics_unscaled();
+        let height_units = v_metrics.ascent - v_metrics.descent;
+        let (scale_x, scale_y) = (scale.x / height_units, scale.y / height_units);
+
+        let baseline = self.fonts.plain.primary().font.v_metrics(scale).ascent;
+
+        let mut pen = 0.0;
+        let glyphs = glyph_buffer
+            .glyph_infos()
+            .iter()
+            .zip(glyph_buffer.glyph_positions())
+            .map(|(info, position)| {
+                let glyph = font
+                    .font
+                    .glyph(GlyphId(info.glyph_id as u16))
+                    .scaled(scale)
+                    .positioned(point(
+                        pen + position.x_offset as f32 * scale_x,
+                        baseline - position.y_offset as f32 * scale_y,
+                    ));
+                pen += position.x_advance as f32 * scale_x;
+
+                glyph
+            })
+            .collect();
+
+        (glyphs, pen)
+    }
+
+    /// Draws any underline and strikethrough across a run of text `width` pixels wide, placed
+    /// using the plain text's primary font so they line up across runs.
+    #[allow(clippy::too_many_arguments)]
+    fn draw_decorations(
+        &self,
+        image: &mut RgbaImage,
+        color: Rgba<u8>,
+        x: i32,
+        y: i32,
+        scale: Scale,
+        style: Style,
+        width: u32,
+    ) {
+        let font = self.fonts.plain.primary();
+
+        let v_metrics = font.font.v_metrics_unscaled();
+        let scale_y = scale.y / (v_metrics.ascent - v_metrics.descent);
+        let baseline = font.font.v_metrics(scale).ascent;
+
+        let decorations = [
+            (style.underline, font.face.underline_metrics()),
+            (style.strikethrough, font.face.strikeout_metrics()),
+        ];
+        for (_, metrics) in decorations.into_iter().filter(|(enabled, _)| *enabled) {
+            let Some(metrics) = metrics else {
+                continue;
+            };
+            if width == 0 {
+                continue;
+            }
+
+            let top = baseline - metrics.position as f32 * scale_y;
+            let thickness = (metrics.thickness as f32 * scale_y).round().max(1.0) as u32;
+
+            drawing::draw_filled_rect_mut(
+                image,
+                Rect::at(x, y + top.round() as i32).of_size(width, thickness),
+                color,
+            );
+        }
+    }
+
+    fn emoji_top(&self, scale: Scale) -> i32 {
+        let ascent = self.fonts.plain.primary().font.v_metrics(scale).ascent;
+        let size = emoji_size(scale) as f32;
+
+        (ascent - size * (1.0 - EMOJI_DESCENT_MULTIPLIER)).max(0.0) as i32
+    }
+}
+
+/// Draws glyphs positioned relative to `(x, y)`, blending them into the image the same way as
+/// [`imageproc::drawing::draw_text_mut`].
+fn draw_glyphs(image: &mut RgbaImage, color: Rgba<u8>, x: i32, y: i32, glyphs: &[PositionedGlyph]) {
+    let (image_width, image_height) = (image.width() as i32, image.height() as i32);
+
+    for glyph in glyphs {
+        let Some(bounding_box) = glyph.pixel_bounding_box() else {
+            continue;
+        };
+
+        glyph.draw(|glyph_x, glyph_y, coverage| {
+            let image_x = x + bounding_box.min.x + glyph_x as i32;
+            let image_y = y + bounding_box.min.y + glyph_y as i32;
+
+            if (0..image_width).contains(&image_x) && (0..image_height).contains(&image_y) {
+                let pixel = image.get_pixel_mut(image_x as u32, image_y as u32);
+                *pixel = pixelops::weighted_sum(*pixel, color, 1.0 - coverage, coverage);
+            }
+        });
+    }
+}
+
+fn emoji_size(scale: Scale) -> u32 {
+    (scale.y * EMOJI_SIZE_MULTIPLIER) as u32
+}
@@ -78,7 +78,7 @@ impl UnsplashClient {
     }
 
     // TODO: implement proper error handling
-    pub async fn get_random_photo(&self, options: GetRandomPhotoOptions) -> Result<RgbImage> {
+    pub async fn get_random_photo(&self, options: GetRandomPhotoOptions) -> Result<Photo> {
         let metadata: HashMap<String, JsonValue> = self
             .unsplash_reqwest_client
             .get(
@@ -96,6 +96,13 @@ impl UnsplashClient {
             .await
             .context("invalid random photo response format")?;
 
+        let id = metadata
+            .get("id")
+            .ok_or(anyhow!("metadata missing `id` key"))?
+            .as_str()
+            .ok_or(anyhow!("invalid data type of `id` metadata key"))?
+            .to_owned();
+
         let url: Url = metadata
             .get("urls")
             .ok_or(anyhow!("metadata missing `urls` key"))?
@@ -111,10 +118,16 @@ impl UnsplashClient {
             .await
             .context("failed to download image")?;
 
-        Ok(image)
+        Ok(Photo { id, image })
     }
 }
 
+#[derive(Debug, Clone)]
+pub struct Photo {
+    pub id: String,
+    pub image: RgbImage,
+}
+
 #[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Hash)]
 #[serde(rename_all = "lowercase")]
 pub enum Orientation {
@@ -0,0 +1,103 @@
+//! Reference images of cards with right-to-left text, checking it's ordered, shaped and wrapped
+//! properly.
+//!
+//! After a change that's meant to alter how these cards look, regenerate the images by running
+//! the tests with `UPDATE_GOLDEN` set, and check them by eye before committing them.
+
+use std::{env, path::PathBuf};
+
+use chrono::NaiveDate;
+use image::{Rgb, RgbImage, RgbaImage};
+use quote_bot::{
+    custom_emoji::CustomEmojiImages,
+    render::{self, Format, RenderOptions},
+};
+
+// the largest difference in any channel of any pixel that's still counted as a match, to allow for
+// floating point differences between platforms
+const MAX_CHANNEL_DIFFERENCE: u8 = 2;
+
+fn render_quote(quote: &str, author: &str) -> RgbaImage {
+    let options = RenderOptions {
+        format: Format::Banner,
+        ..Default::default()
+    };
+
+    render::render(
+        &RgbImage::from_pixel(300, 100, Rgb([40, 60, 90])),
+        quote,
+        author,
+        NaiveDate::from_ymd_opt(2024, 3, 1).unwrap(),
+        &CustomEmojiImages::new(),
+        None,
+        &options,
+    )
+    .image
+}
+
+fn assert_matches_golden(name: &str, image: &RgbaImage) {
+    let golden_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests")
+        .join("golden")
+        .join(format!("{name}.png"));
+
+    if env::var_os("UPDATE_GOLDEN").is_some() {
+        image.save(&golden_path).unwrap();
+        return;
+    }
+
+    let golden = image::open(&golden_path)
+        .unwrap_or_else(|err| {
+            panic!(
+                "failed to open `{}`, run with `UPDATE_GOLDEN` set to create it: {err}",
+                golden_path.display()
+            )
+        })
+        .into_rgba8();
+
+    let matches =
+        golden.dimensions() == image.dimensions()
+            && golden
+                .pixels()
+                .zip(image.pixels())
+                .all(|(expected, actual)| {
+                    expected.0.iter().zip(actual.0).all(|(expected, actual)| {
+                        expected.abs_diff(actual) <= MAX_CHANNEL_DIFFERENCE
+                    })
+                });
+
+    if !matches {
+        let actual_path = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(format!("{name}.png"));
+        image.save(&actual_path).unwrap();
+
+        panic!(
+            "`{name}` doesn't match `{}`, it was rendered to `{}` instead",
+            golden_path.display(),
+            actual_path.display()
+        );
+    }
+}
+
+#[test]
+fn pure_rtl() {
+    let image = render_quote("שלום עולם, מה שלומך היום?", "דנה");
+
+    assert_matches_golden("pure_rtl", &image);
+}
+
+#[test]
+fn mixed_rtl_and_ltr() {
+    let image = render_quote("The word שלום means peace, as does سلام in Arabic.", "Alex");
+
+    assert_matches_golden("mixed_rtl_and_ltr", &image);
+}
+
+#[test]
+fn wrapped_rtl() {
+    let image = render_quote(
+        "الحياة ليست انتظار مرور العاصفة، بل تعلم الرقص تحت المطر مهما طال الليل وبعد الطريق",
+        "مجهول",
+    );
+
+    assert_matches_golden("wrapped_rtl", &image);
+}